@@ -18,8 +18,13 @@ fn main() {
 
     // Calling the function
     let inst = f.instantiate(&mut tenv).function();
-    inst.call(&mut tenv, &index, &[concrete("int"), concrete("float")])
-        .unwrap();
+    inst.call(
+        &mut tenv,
+        &index,
+        &(),
+        &[concrete("int"), concrete("float")],
+    )
+    .unwrap();
 
     // Remove any tvar indirection
     let returns = tenv.concretify_type(&inst.returns);
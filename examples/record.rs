@@ -1,5 +1,5 @@
 use lumina_typesystem::frontend::Product;
-use lumina_typesystem::{ErrorHandler, TEnv, TraitIndex, TypeContext};
+use lumina_typesystem::{ErrorHandler, TEnv, TraitIndex, TypeContext, TypeDecls};
 
 #[path = "shared.rs"]
 mod shared;
@@ -15,16 +15,28 @@ fn main() {
     // The product type we want to work with
     let product = Product::new(
         "User",
-        vec![concrete("string"), concrete("int"), generic('a')],
+        vec![
+            ("name", concrete("string")),
+            ("age", concrete("int")),
+            ("data", generic('a')),
+        ],
     )
-    .to_foreign(vec!['a'].into_iter().collect());
+    .to_foreign(vec!['a'].into_iter().collect())
+    .expect("invalid declaration")
+    .0;
 
     // Instantiate the product type
     let mut inst = product.instantiate(&mut tenv);
 
     // If the type parameter is annotated, then we set that via the mapping.
     inst.mapping_mut()
-        .annotate_types(&mut tenv, &index, &[concrete("instead_of_a")])
+        .annotate_types(
+            &mut tenv,
+            &index,
+            &(),
+            &TypeDecls::new(),
+            &[concrete("instead_of_a")],
+        )
         .expect("constraint not satisfied");
 
     // Constructing the product type
@@ -35,9 +47,9 @@ fn main() {
 
     // Access fields from the product type
     {
-        let name = inst.field(0);
-        let age = inst.field(1);
-        let data = inst.field(2);
+        let name = inst.field(&"name").unwrap();
+        let age = inst.field(&"age").unwrap();
+        let data = inst.field(&"data").unwrap();
 
         let mut tctx = TypeContext::new(&mut tenv, &index, ErrorHandler::Expensive);
         tctx.check_types(
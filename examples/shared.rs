@@ -12,6 +12,9 @@ impl lumina_typesystem::TypeData for TypeData {
     type Generic = char;
     type Trait = &'static str;
     type Association = &'static str;
+    type Method = &'static str;
+    type Function = &'static str;
+    type Field = &'static str;
 
     type Meta = ();
 
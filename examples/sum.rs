@@ -1,5 +1,5 @@
 use lumina_typesystem::frontend::Sum;
-use lumina_typesystem::{TEnv, TraitIndex};
+use lumina_typesystem::{TEnv, TraitIndex, TypeDecls};
 
 #[path = "shared.rs"]
 mod shared;
@@ -14,19 +14,28 @@ fn main() {
 
     // The product type we want to work with
     let sum = Sum::new("Result", vec![vec![generic('a')], vec![generic('e')]])
-        .to_foreign(vec!['a', 'e'].into_iter().collect());
+        .to_foreign(vec!['a', 'e'].into_iter().collect())
+        .expect("invalid declaration")
+        .0;
 
     // Instantiate the product type
     let mut inst = sum.instantiate(&mut tenv);
 
     // If the type parameter is annotated, then we set that via the mapping.
     inst.mapping_mut()
-        .annotate_gid(&mut tenv, &index, &'a', concrete("instead_of_a"))
+        .annotate_gid(
+            &mut tenv,
+            &index,
+            &(),
+            &TypeDecls::new(),
+            &'a',
+            concrete("instead_of_a"),
+        )
         .expect("constraint not satisfied");
 
     // Constructing a variant of the sum type
-    let f = inst.constructor((), 0);
-    f.call(&mut tenv, &index, &[concrete("instead_of_a")])
+    let f = inst.constructor(&mut tenv, (), 0);
+    f.call(&mut tenv, &index, &(), &[concrete("instead_of_a")])
         .unwrap();
 
     println!(
@@ -1,8 +1,15 @@
-use crate::{query, Constraint, RefID, TEnv, TraitIndex, Type, TypeData, TypeKind, Types};
+use crate::decls::same_constraint;
+use crate::{
+    query, Constraint, Kind, KindLookup, RefID, TEnv, TraitIndex, Type, TypeData, TypeDecls,
+    TypeKind, Types,
+};
+use std::cmp::Ordering;
 
 pub struct TypeContext<'a, D: TypeData> {
     pub(crate) tenv: &'a mut TEnv<D>,
     pub(crate) traits: &'a TraitIndex<D>,
+    kinds: &'a dyn KindLookup<D>,
+    decls: Option<&'a TypeDecls<D>>,
     ehandler: ErrorHandler,
 }
 
@@ -26,6 +33,24 @@ pub enum Error<D: TypeData> {
 
     ConstraintNotMet(Type<D>, Constraint<D>, Vec<query::Contender>),
 
+    /// A rigid generic was assigned to a reference which lives outside of its scope
+    EscapingGeneric {
+        generic: D::Generic,
+        into: RefID,
+    },
+
+    /// A higher-kinded reference was given a type with too few parameters to be applied to it
+    KindMissmatch {
+        type_: Type<D>,
+        expected: Kind,
+    },
+
+    /// A declared type was given more parameters than it has generics
+    ArityMissmatch {
+        type_: Type<D>,
+        expected: usize,
+    },
+
     /// When the `cheap_error` flag is set
     Disgarded,
 }
@@ -34,19 +59,57 @@ type CheckResult<D> = Result<(), Error<D>>;
 
 impl<'a, D: TypeData> TypeContext<'a, D> {
     pub fn new(tenv: &'a mut TEnv<D>, traits: &'a TraitIndex<D>, ehandler: ErrorHandler) -> Self {
-        Self { tenv, traits, ehandler }
+        Self {
+            tenv,
+            traits,
+            kinds: &(),
+            decls: None,
+            ehandler,
+        }
+    }
+
+    /// Select implementations of higher-kinded traits for applications of their implementor
+    pub fn with_kinds(mut self, kinds: &'a dyn KindLookup<D>) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    /// Verify that the declared types are given the right amount of parameters
+    pub fn with_decls(mut self, decls: &'a TypeDecls<D>) -> Self {
+        self.decls = Some(decls);
+        self
     }
 
     pub fn check(&mut self, left: &Type<D>, right: &Type<D>) -> CheckResult<D> {
+        self.arity(left)?;
+        self.arity(right)?;
+
         match (&left.constr, &right.constr) {
+            // The given type needs to be at least as polymorphic as the expected one, so the
+            // expected type is skolemized while the given type is instantiated.
+            (_, TypeKind::Forall(bound)) => {
+                let scope = self.tenv.enter_scope(&bound.iter().cloned().collect());
+                let result = self.check(left, &scope.apply_type(&right.params[0]));
+                self.tenv.leave_scope();
+                result
+            }
+            (TypeKind::Forall(bound), _) => {
+                let rids = bound.iter().map(|_| self.tenv.spawn()).collect::<Vec<_>>();
+                let body = left.params[0].map_free_constr(&mut |constr| match constr {
+                    TypeKind::Generic(gid) => match bound.iter().position(|g| g == gid) {
+                        Some(i) => TypeKind::Ref(rids[i]),
+                        None => constr.clone(),
+                    },
+                    _ => constr.clone(),
+                });
+                self.check(&body, right)
+            }
+
             (TypeKind::Ref(lrid), TypeKind::Ref(rrid)) => {
                 if *lrid == *rrid {
                     self.params(left, right)
                 } else {
-                    match (
-                        self.tenv.get_type(*lrid).cloned(),
-                        self.tenv.get_type(*rrid).cloned(),
-                    ) {
+                    match (self.assigned(*lrid, left), self.assigned(*rrid, right)) {
                         (Some(l), Some(r)) => self.check(&l, &r),
                         (Some(l), None) => self.check(&l, right),
                         (None, Some(r)) => self.check(left, &r),
@@ -63,12 +126,18 @@ impl<'a, D: TypeData> TypeContext<'a, D> {
             (TypeKind::Generic(lgid), TypeKind::Generic(rgid)) if lgid == rgid => {
                 self.params(left, right)
             }
+            (TypeKind::Opaque(lid), TypeKind::Opaque(rid)) if lid == rid => {
+                self.params(left, right)
+            }
+            (TypeKind::Skolem(lid), TypeKind::Skolem(rid)) if lid == rid => {
+                self.params(left, right)
+            }
 
-            (TypeKind::Ref(lrid), _) => match self.tenv.get_type(*lrid).cloned() {
+            (TypeKind::Ref(lrid), _) => match self.assigned(*lrid, left) {
                 Some(assigned) => self.check(&assigned, right),
                 None => self.assign_to_ref(*lrid, &left.params, right.clone()),
             },
-            (_, TypeKind::Ref(rrid)) => match self.tenv.get_type(*rrid).cloned() {
+            (_, TypeKind::Ref(rrid)) => match self.assigned(*rrid, right) {
                 Some(assigned) => self.check(left, &assigned),
                 None => self.assign_to_ref(*rrid, &right.params, left.clone()),
             },
@@ -81,6 +150,36 @@ impl<'a, D: TypeData> TypeContext<'a, D> {
         }
     }
 
+    /// Under-applied types are left to the kind checks, since they may be partially applied to a
+    /// higher-kinded reference.
+    pub(crate) fn arity(&mut self, t: &Type<D>) -> CheckResult<D> {
+        if let (Some(decls), TypeKind::Concrete(constr)) = (self.decls, &t.constr) {
+            if let Some(decl) = decls.get(constr) {
+                if t.params.len() > decl.arity() {
+                    return Err(self.ehandler.arity_missmatch(self.tenv, t, decl.arity()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn arity_of_params(&mut self, t: &Type<D>) -> CheckResult<D> {
+        for param in &t.params {
+            self.arity(param)?;
+            self.arity_of_params(param)?;
+        }
+        Ok(())
+    }
+
+    /// The assigned type of a reference, applied to the higher-kinded params of its usage
+    fn assigned(&self, rid: RefID, usage: &Type<D>) -> Option<Type<D>> {
+        self.tenv.get_type(rid).map(|t| {
+            let mut t = t.clone();
+            t.params.extend(usage.params.iter().cloned());
+            t
+        })
+    }
+
     pub fn check_types(&mut self, left: &Types<D>, right: &Types<D>) -> Result<(), Error<D>> {
         left.iter()
             .zip(right)
@@ -93,6 +192,8 @@ impl<'a, D: TypeData> TypeContext<'a, D> {
         rid_params: &Types<D>,
         given: Type<D>,
     ) -> CheckResult<D> {
+        // The given type won't be walked by `check` once assigned
+        self.arity_of_params(&given)?;
         self.check_constraints_then_assign(rid, rid_params, given)
     }
 
@@ -112,28 +213,80 @@ impl<'a, D: TypeData> TypeContext<'a, D> {
                 .cloned()
                 .collect();
 
+            let deferred = [lrid, rrid]
+                .iter()
+                .flat_map(|rid| std::mem::take(&mut self_.tenv.get_mut(*rid).deferred))
+                .collect();
+
             let mrid = self_.tenv.spawn_with_cons(merged);
+            let level = self_.tenv.get(lrid).level.min(self_.tenv.get(rrid).level);
+            self_.tenv.get_mut(mrid).level = level;
+            self_.tenv.get_mut(mrid).deferred = deferred;
             let type_ = Type::reference(left.meta.clone(), mrid, vec![]);
             self_.tenv.assign(lrid, type_.clone());
             self_.tenv.assign(rrid, type_);
         };
 
-        match (left.params.len(), right.params.len()) {
-            (x, y) if x == y => {
+        match left.params.len().cmp(&right.params.len()) {
+            Ordering::Equal => {
                 apply_assignments(self);
                 self.params(left, right)
             }
-            // Do we need to do another RefID?
-            //
-            // Or perhaps we do want to assign one to the other?
-            //
-            // Or do we want to fail with inconsistent HKT?
-            // Perhaps we want to fail with inconsistent if the other had constraints?
-            (_, 0) => todo!("what to do with left.params"),
-            (0, _) => todo!("what to do with right.params"),
+            // `'l p q ~ 'r x` means that `'r` is `'l` partially applied to the leading
+            // params which `'r` is missing, and the trailing params need to match pairwise.
+            Ordering::Greater => {
+                let at = left.params.len() - right.params.len();
+                self.assign_partial_application(rrid, lrid, left, at)?;
+                self.check_types(&left.params[at..], &right.params)
+            }
+            Ordering::Less => {
+                let at = right.params.len() - left.params.len();
+                self.assign_partial_application(lrid, rrid, right, at)?;
+                self.check_types(&left.params, &right.params[at..])
+            }
+        }
+    }
+
+    fn assign_partial_application(
+        &mut self,
+        under_applied: RefID,
+        constr: RefID,
+        applied: &Type<D>,
+        at: usize,
+    ) -> CheckResult<D> {
+        let partial = Type::reference(applied.meta.clone(), constr, applied.params[..at].to_vec());
 
-            _ => Err(self.ehandler.param_amount_missmatch(self.tenv, left, right)),
+        self.escape_into(under_applied, &partial)?;
+
+        // The constraints are for the partially applied type, which we can't check until the
+        // type constructor is known. So they're carried over to the constructor's reference.
+        let entry = self.tenv.get_mut(under_applied);
+        let mut deferred = std::mem::take(&mut entry.deferred);
+        deferred.extend(
+            std::mem::take(&mut entry.constraints)
+                .into_iter()
+                .map(|con| (partial.clone(), con)),
+        );
+        self.tenv.get_mut(constr).deferred.extend(deferred);
+
+        self.tenv.assign(under_applied, partial);
+        Ok(())
+    }
+
+    /// Check the constraints deferred by partial applications of a reference which was just
+    /// assigned, or carry them over if it was assigned to another reference.
+    fn recheck_deferred(&mut self, rid: RefID) -> CheckResult<D> {
+        let deferred = std::mem::take(&mut self.tenv.get_mut(rid).deferred);
+
+        for (partial, con) in deferred {
+            let given = self.tenv.concretify_type(&partial);
+            match given.constr {
+                TypeKind::Ref(constr) => self.tenv.get_mut(constr).deferred.push((partial, con)),
+                _ => self.check_constraint(&given, &con)?,
+            }
         }
+
+        Ok(())
     }
 
     fn params(&mut self, left: &Type<D>, right: &Type<D>) -> CheckResult<D> {
@@ -144,6 +297,53 @@ impl<'a, D: TypeData> TypeContext<'a, D> {
         }
     }
 
+    fn escape_into(&mut self, rid: RefID, t: &Type<D>) -> CheckResult<D> {
+        let level = self.tenv.get(rid).level;
+        self.tenv
+            .lower_to(t, level)
+            .map_err(|generic| Error::EscapingGeneric { generic, into: rid })
+    }
+
+    /// Whether the constraint is met by the assumptions of a rigid generic
+    ///
+    /// Each assumption of the same trait is tried against a scratch type environment, and the
+    /// first one which unifies is kept.
+    fn assumed(&mut self, given: &Type<D>, con: &Constraint<D>) -> bool {
+        let assumptions = match &given.constr {
+            TypeKind::Skolem(id) if given.params.is_empty() => self
+                .tenv
+                .skolem(*id)
+                .assumptions
+                .iter()
+                .filter(|assumption| {
+                    assumption.trid == con.trid && assumption.params.len() == con.params.len()
+                })
+                .cloned()
+                .collect(),
+            _ => vec![],
+        };
+
+        for assumption in assumptions {
+            let mut scratch = self.tenv.clone();
+            let unified = TypeContext {
+                tenv: &mut scratch,
+                traits: self.traits,
+                kinds: self.kinds,
+                decls: self.decls,
+                ehandler: ErrorHandler::Cheap,
+            }
+            .check_types(&con.params, &assumption.params)
+            .is_ok();
+
+            if unified {
+                *self.tenv = scratch;
+                return true;
+            }
+        }
+
+        false
+    }
+
     pub(crate) fn check_constraints_then_assign(
         &mut self,
         rid: RefID,
@@ -152,55 +352,81 @@ impl<'a, D: TypeData> TypeContext<'a, D> {
         mut given: Type<D>,
     ) -> CheckResult<D> {
         if !rid_params.is_empty() {
-            // NOTE: this means that we *need* to have consistent HKT usage.
-            //
-            // if we don't, the errors will be confusing. So; we might want to edge-case that.
+            if given.params.len() < rid_params.len() {
+                let expected = Kind::arity(rid_params.len());
+                return Err(self.ehandler.kind_missmatch(self.tenv, &given, expected));
+            }
+
             let at = given.params.len() - rid_params.len();
             let corresponding_of_higher_kinded = given.params.split_off(at);
             self.check_types(&corresponding_of_higher_kinded, rid_params)?;
         }
 
+        self.escape_into(rid, &given)?;
+
         let constrs = self.tenv.constraints(rid).to_vec();
 
         for con in constrs {
-            let compatible = self
-                .traits
-                .select(self.tenv, con.trid.clone(), &con.params, &given);
-
-            match compatible {
-                Err(contendors) => {
-                    return Err(Error::ConstraintNotMet(
-                        given.clone(),
-                        con.clone(),
-                        contendors,
-                    ))
-                }
-                Ok(mut matches) => {
-                    let query::QuerySuccess { impl_, tenv, .. } = matches.remove(0);
-
-                    if !matches.is_empty() {
-                        // let's try actually creating this, otherwise I have no idea how to
-                        // phrase/handle it.
-                        //
-                        // But ye, we definitely *need* this because otherwise we can't just
-                        // randomly select the first type environment if it's not decisive.
-                        todo!("ET: conflicting implementations/inference error??");
-                    }
+            self.check_constraint(&given, &con)?;
+        }
 
-                    *self.tenv = tenv;
+        self.tenv.assign(rid, given);
 
-                    if !impl_.associated.is_empty() {
-                        unimplemented!(
-                            "we need to port this association instantiation to the new api"
-                        );
-                    };
+        self.recheck_deferred(rid)
+    }
+
+    /// Require the type to satisfy the constraint, references not yet infered are given the
+    /// constraint to be checked once they are.
+    pub(crate) fn constrain(&mut self, given: &Type<D>, con: &Constraint<D>) -> CheckResult<D> {
+        let given = self.tenv.concretify_type(given);
+        match given.constr {
+            TypeKind::Ref(rid) if given.params.is_empty() => {
+                let constraints = self.tenv.constraints_mut(rid);
+                if !constraints.iter().any(|c| same_constraint(c, con)) {
+                    constraints.push(con.clone());
                 }
+                Ok(())
             }
+            _ => self.check_constraint(&given, con),
         }
+    }
 
-        self.tenv.assign(rid, given);
+    fn check_constraint(&mut self, given: &Type<D>, con: &Constraint<D>) -> CheckResult<D> {
+        if self.assumed(given, con) {
+            return Ok(());
+        }
 
-        Ok(())
+        let compatible =
+            self.traits
+                .select(self.tenv, self.kinds, con.trid.clone(), &con.params, given);
+
+        match compatible {
+            Err(contendors) => Err(Error::ConstraintNotMet(
+                given.clone(),
+                con.clone(),
+                contendors,
+            )),
+            Ok(mut matches) => {
+                let query::QuerySuccess { impl_, tenv, .. } = matches.remove(0);
+
+                if !matches.is_empty() {
+                    // let's try actually creating this, otherwise I have no idea how to
+                    // phrase/handle it.
+                    //
+                    // But ye, we definitely *need* this because otherwise we can't just
+                    // randomly select the first type environment if it's not decisive.
+                    todo!("ET: conflicting implementations/inference error??");
+                }
+
+                *self.tenv = tenv;
+
+                if !impl_.associated.is_empty() {
+                    unimplemented!("we need to port this association instantiation to the new api");
+                };
+
+                Ok(())
+            }
+        }
     }
 }
 
@@ -225,6 +451,46 @@ impl ErrorHandler {
         }
     }
 
+    pub fn kind_missmatch<D: TypeData>(
+        &mut self,
+        tenv: &TEnv<D>,
+        type_: &Type<D>,
+        expected: Kind,
+    ) -> Error<D> {
+        match self {
+            ErrorHandler::Cheap => Error::Disgarded,
+            ErrorHandler::PanicOnError => panic!(
+                "kind missmatch: {} can not be of kind {}",
+                tenv.concretify_type(type_),
+                expected
+            ),
+            ErrorHandler::Expensive => Error::KindMissmatch {
+                type_: type_.clone(),
+                expected,
+            },
+        }
+    }
+
+    pub fn arity_missmatch<D: TypeData>(
+        &mut self,
+        tenv: &TEnv<D>,
+        type_: &Type<D>,
+        expected: usize,
+    ) -> Error<D> {
+        match self {
+            ErrorHandler::Cheap => Error::Disgarded,
+            ErrorHandler::PanicOnError => panic!(
+                "arity missmatch: {} takes {} parameters",
+                tenv.concretify_type(type_),
+                expected
+            ),
+            ErrorHandler::Expensive => Error::ArityMissmatch {
+                type_: type_.clone(),
+                expected,
+            },
+        }
+    }
+
     pub fn param_amount_missmatch<D: TypeData>(
         &mut self,
         tenv: &TEnv<D>,
@@ -0,0 +1,228 @@
+use crate::frontend::{ForeignFunction, ForeignProduct, ForeignSum};
+use crate::{
+    Constraint, Generics, Kind, KindLookup, Type, TypeData, TypeKind, TypeVisitor, Variance,
+};
+use std::collections::HashMap;
+
+/// The declaration of a user-defined type
+#[derive(Debug, Clone)]
+pub enum TypeDecl<D: TypeData> {
+    Product(ForeignProduct<D>),
+    Sum(ForeignSum<D>),
+}
+
+impl<D: TypeData> TypeDecl<D> {
+    pub fn generics(&self) -> &Generics<D> {
+        match self {
+            TypeDecl::Product(product) => &product.generics,
+            TypeDecl::Sum(sum) => &sum.generics,
+        }
+    }
+
+    /// Amount of type parameters needed to fully apply the type
+    pub fn arity(&self) -> usize {
+        self.generics().len()
+    }
+
+    /// The kind of the type constructor, with generics of unknown kind assumed to be `*`
+    pub fn kind(&self) -> Kind {
+        let generics = self.generics();
+        let params = generics
+            .iter()
+            .map(|(gid, _)| generics.kind(gid).cloned().unwrap_or(Kind::Type))
+            .collect::<Vec<_>>();
+
+        params.into_iter().rev().fold(Kind::Type, |ret, param| {
+            Kind::Arrow(Box::new(param), Box::new(ret))
+        })
+    }
+}
+
+/// Registry of the declared types, for looking up their definitions by identifier
+#[derive(Debug, Clone)]
+pub struct TypeDecls<D: TypeData> {
+    decls: HashMap<D::Concrete, TypeDecl<D>>,
+    builtin_variances: HashMap<D::Concrete, Vec<Variance>>,
+}
+
+impl<D: TypeData> TypeDecls<D> {
+    pub fn new() -> Self {
+        Self {
+            decls: HashMap::new(),
+            builtin_variances: HashMap::new(),
+        }
+    }
+
+    /// Returns the previous declaration if the identifier was already declared
+    pub fn declare_product(&mut self, product: ForeignProduct<D>) -> Option<TypeDecl<D>> {
+        let identifier = product.product.identifier.clone();
+        self.decls.insert(identifier, TypeDecl::Product(product))
+    }
+
+    /// Returns the previous declaration if the identifier was already declared
+    pub fn declare_sum(&mut self, sum: ForeignSum<D>) -> Option<TypeDecl<D>> {
+        let identifier = sum.sum.identifier.clone();
+        self.decls.insert(identifier, TypeDecl::Sum(sum))
+    }
+
+    /// Declare the variance of the parameters of a type which has no declaration, such as the
+    /// parameters and return type of a builtin function type.
+    pub fn declare_variance(&mut self, constr: D::Concrete, variances: Vec<Variance>) {
+        self.builtin_variances.insert(constr, variances);
+    }
+
+    pub(crate) fn builtin_variance(&self, constr: &D::Concrete) -> Option<&[Variance]> {
+        self.builtin_variances.get(constr).map(Vec::as_slice)
+    }
+
+    pub fn get(&self, constr: &D::Concrete) -> Option<&TypeDecl<D>> {
+        self.decls.get(constr)
+    }
+
+    pub fn product(&self, constr: &D::Concrete) -> Option<&ForeignProduct<D>> {
+        match self.get(constr)? {
+            TypeDecl::Product(product) => Some(product),
+            TypeDecl::Sum(_) => None,
+        }
+    }
+
+    pub fn sum(&self, constr: &D::Concrete) -> Option<&ForeignSum<D>> {
+        match self.get(constr)? {
+            TypeDecl::Sum(sum) => Some(sum),
+            TypeDecl::Product(_) => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&D::Concrete, &TypeDecl<D>)> {
+        self.decls.iter()
+    }
+
+    /// Extend the generics of the signature with the constraints implied by its parameter types.
+    ///
+    /// Taking `(set a)` as parameter where `set` is declared with `a is Ord` means the body may
+    /// assume `a is Ord`, while callers are required to prove it as if it was declared.
+    pub fn with_implied_bounds(&self, f: &ForeignFunction<D>) -> ForeignFunction<D> {
+        let mut generics = f.generics.clone();
+        for t in &f.function.ptypes {
+            self.implied_bounds(t, &mut generics);
+        }
+
+        ForeignFunction {
+            generics,
+            function: f.function.clone(),
+        }
+    }
+
+    fn implied_bounds(&self, t: &Type<D>, generics: &mut Generics<D>) {
+        for param in &t.params {
+            self.implied_bounds(param, generics);
+        }
+
+        let decl = match &t.constr {
+            TypeKind::Concrete(constr) => match self.get(constr) {
+                Some(decl) => decl,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let declared = decl.generics();
+        for ((_, constrs), given) in declared.iter().zip(&t.params) {
+            let gid = match &given.constr {
+                TypeKind::Generic(gid)
+                    if given.params.is_empty() && generics.contains(gid.clone()) =>
+                {
+                    gid.clone()
+                }
+                _ => continue,
+            };
+
+            for con in constrs {
+                let implied = match substitute(declared, &Generics::new(), &t.params, con) {
+                    Some(con) => con,
+                    None => continue,
+                };
+
+                let known = generics.constraints(&gid);
+                if !known.iter().any(|k| same_constraint(k, &implied)) {
+                    generics.update_with_con(gid.clone(), implied);
+                }
+            }
+        }
+    }
+}
+
+/// Replace the generics of a declaration with the parameters it was given
+///
+/// Generics bound by `local`, such as existentials, shadow the declared generics and are kept
+/// as-is along with any generic which isn't declared. Fails if the type refers to a declared
+/// generic which wasn't given.
+pub(crate) fn substitute<D: TypeData, T: TypeVisitor<D>>(
+    declared: &Generics<D>,
+    local: &Generics<D>,
+    params: &[Type<D>],
+    t: &T,
+) -> Option<T> {
+    let mut missing = false;
+    let t = t.map_types(|meta, constr, tparams| match constr {
+        TypeKind::Generic(gid) if !local.contains(gid.clone()) => {
+            match declared.position(gid.clone()) {
+                Some(i) => match params.get(i) {
+                    Some(given) => {
+                        let mut t = given.clone();
+                        t.params.extend(tparams);
+                        t
+                    }
+                    None => {
+                        missing = true;
+                        Type::generic(meta, gid.clone(), tparams)
+                    }
+                },
+                None => Type::generic(meta, gid.clone(), tparams),
+            }
+        }
+        _ => Type {
+            meta,
+            constr: constr.clone(),
+            params: tparams,
+        },
+    });
+
+    if missing {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+pub(crate) fn same_constraint<D: TypeData>(left: &Constraint<D>, right: &Constraint<D>) -> bool {
+    left.trid == right.trid
+        && left.params.len() == right.params.len()
+        && left
+            .params
+            .iter()
+            .zip(&right.params)
+            .all(|(l, r)| same_type(l, r))
+}
+
+fn same_type<D: TypeData>(left: &Type<D>, right: &Type<D>) -> bool {
+    left.constr == right.constr
+        && left.params.len() == right.params.len()
+        && left
+            .params
+            .iter()
+            .zip(&right.params)
+            .all(|(l, r)| same_type(l, r))
+}
+
+impl<D: TypeData> Default for TypeDecls<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: TypeData> KindLookup<D> for TypeDecls<D> {
+    fn concrete(&self, constr: &D::Concrete) -> Option<Kind> {
+        self.get(constr).map(TypeDecl::kind)
+    }
+}
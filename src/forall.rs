@@ -1,4 +1,4 @@
-use crate::{Mapping, TEnv, Type, TypeData, TypeKind, TypeVisitor, TypesBuf};
+use crate::{Kind, Mapping, TEnv, Type, TypeData, TypeKind, TypeVisitor, TypesBuf};
 use itertools::Itertools;
 use std::fmt;
 use std::iter::FromIterator;
@@ -13,11 +13,16 @@ pub struct Constraint<D: TypeData> {
 #[derive(Debug, Clone, Default)]
 pub struct Generics<D: TypeData> {
     inner: Vec<(D::Generic, Vec<Constraint<D>>)>, // constrs?
+    // explicitly annotated kinds, generics without one are infered
+    kinds: Vec<(D::Generic, Kind)>,
 }
 
 impl<D: TypeData> Generics<D> {
     pub fn new() -> Self {
-        Self { inner: vec![] }
+        Self {
+            inner: vec![],
+            kinds: vec![],
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -38,6 +43,16 @@ impl<D: TypeData> Generics<D> {
     pub fn insert_with_con(&mut self, gid: D::Generic, con: Vec<Constraint<D>>) {
         self.inner.push((gid, con));
     }
+    pub fn insert_with_kind(&mut self, gid: D::Generic, kind: Kind) {
+        self.set_kind(gid.clone(), kind);
+        self.inner.push((gid, vec![]));
+    }
+    pub fn set_kind(&mut self, gid: D::Generic, kind: Kind) {
+        match self.kinds.iter_mut().find(|(g, _)| *g == gid) {
+            Some((_, k)) => *k = kind,
+            None => self.kinds.push((gid, kind)),
+        }
+    }
     pub fn update_with_cons(&mut self, gid: D::Generic, cons: Vec<Constraint<D>>) {
         if let Some(i) = self.inner.iter().position(|(g, _)| *g == gid) {
             self.inner[i].1.extend(cons.into_iter());
@@ -68,6 +83,12 @@ impl<D: TypeData> Generics<D> {
         })
     }
 
+    pub fn kind(&self, gid: &D::Generic) -> Option<&Kind> {
+        self.kinds
+            .iter()
+            .find_map(|(g, kind)| if g == gid { Some(kind) } else { None })
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &(D::Generic, Vec<Constraint<D>>)> {
         self.inner.iter()
     }
@@ -86,6 +107,9 @@ impl<D: TypeData> Generics<D> {
         for (gid, _) in self.iter() {
             let rid = tenv.spawn();
             mapping.assign(gid.clone(), rid);
+            if let Some(kind) = self.kind(gid) {
+                mapping.set_kind(gid.clone(), kind.clone());
+            }
         }
 
         for (gid, constrs) in self.iter() {
@@ -101,7 +125,8 @@ impl<D: TypeData> Generics<D> {
     }
 
     pub fn extend(&mut self, other: &Self) {
-        self.inner.extend(other.inner.iter().cloned())
+        self.inner.extend(other.inner.iter().cloned());
+        self.kinds.extend(other.kinds.iter().cloned());
     }
 }
 
@@ -109,6 +134,7 @@ impl<D: TypeData> FromIterator<D::Generic> for Generics<D> {
     fn from_iter<I: IntoIterator<Item = D::Generic>>(iter: I) -> Self {
         Generics {
             inner: iter.into_iter().map(|gid| (gid, vec![])).collect(),
+            kinds: vec![],
         }
     }
 }
@@ -134,6 +160,7 @@ impl<D: TypeData> TypeVisitor<D> for Generics<D> {
                     )
                 })
                 .collect(),
+            kinds: self.kinds.clone(),
         }
     }
 }
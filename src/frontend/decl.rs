@@ -0,0 +1,142 @@
+use super::*;
+use std::fmt;
+
+/// A declaration which can't be instantiated
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeclError<D: TypeData> {
+    UndeclaredGeneric(D::Generic),
+    /// `Self` only has meaning inside of traits
+    SelfInDeclaration,
+    /// Declarations may not contain types which are still being infered
+    RefInDeclaration(RefID),
+    /// Declarations may not contain the rigid types of a scope being checked
+    SkolemInDeclaration(SkolemID),
+    /// Inference holes may only be used in signatures
+    HoleInDeclaration,
+    /// A product declaring more than one field by the same name
+    DuplicateField(D::Field),
+}
+
+/// A declaration which is valid but most likely a mistake
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeclWarning<D: TypeData> {
+    UnusedGeneric(D::Generic),
+}
+
+/// A declaration along with its warnings, or the errors making it invalid
+pub type Validated<T, D> = Result<(T, Vec<DeclWarning<D>>), Vec<DeclError<D>>>;
+
+/// Checks that the types of a declaration only refer to the generics it declared
+pub(crate) struct DeclValidator<'a, D: TypeData> {
+    generics: &'a Generics<D>,
+    used: Vec<D::Generic>,
+    errors: Vec<DeclError<D>>,
+}
+
+impl<'a, D: TypeData> DeclValidator<'a, D> {
+    pub(crate) fn new(generics: &'a Generics<D>) -> Self {
+        let mut this = Self {
+            generics,
+            used: vec![],
+            errors: vec![],
+        };
+
+        for (_, constrs) in generics.iter() {
+            for con in constrs {
+                for t in &con.params {
+                    this.type_(t, &Generics::new());
+                }
+            }
+        }
+
+        // Generics referenced from the constraints of others aren't considered used by that alone
+        this.used.clear();
+
+        this
+    }
+
+    /// Validate a type of the declaration with additional generics in scope
+    pub(crate) fn type_(&mut self, t: &Type<D>, local: &Generics<D>) {
+        t.map_free_constr(&mut |constr| {
+            match constr {
+                TypeKind::Generic(gid) if local.contains(gid.clone()) => {}
+                TypeKind::Generic(gid) if self.generics.contains(gid.clone()) => {
+                    if !self.used.contains(gid) {
+                        self.used.push(gid.clone());
+                    }
+                }
+                TypeKind::Generic(gid) => self.error(DeclError::UndeclaredGeneric(gid.clone())),
+                TypeKind::Self_ => self.error(DeclError::SelfInDeclaration),
+                TypeKind::Ref(rid) => self.error(DeclError::RefInDeclaration(*rid)),
+                TypeKind::Skolem(id) => self.error(DeclError::SkolemInDeclaration(*id)),
+                TypeKind::Hole => self.error(DeclError::HoleInDeclaration),
+                TypeKind::Concrete(_)
+                | TypeKind::Object(_)
+                | TypeKind::Opaque(_)
+                | TypeKind::Forall(_) => {}
+            }
+            constr.clone()
+        });
+    }
+
+    /// Validate generics local to a part of the declaration, such as existentials
+    pub(crate) fn local_generics(&mut self, local: &Generics<D>) {
+        for (_, constrs) in local.iter() {
+            for con in constrs {
+                for t in &con.params {
+                    self.type_(t, local);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn duplicate_field(&mut self, name: D::Field) {
+        self.error(DeclError::DuplicateField(name))
+    }
+
+    fn error(&mut self, err: DeclError<D>) {
+        if !self.errors.contains(&err) {
+            self.errors.push(err);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Result<Vec<DeclWarning<D>>, Vec<DeclError<D>>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+
+        Ok(self
+            .generics
+            .iter()
+            .filter(|(gid, _)| !self.used.contains(gid))
+            .map(|(gid, _)| DeclWarning::UnusedGeneric(gid.clone()))
+            .collect())
+    }
+}
+
+impl<D: TypeData> fmt::Display for DeclError<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeclError::UndeclaredGeneric(gid) => write!(f, "generic {} is not declared", gid),
+            DeclError::SelfInDeclaration => "`self` may only be used in traits".fmt(f),
+            DeclError::RefInDeclaration(rid) => {
+                write!(f, "type {} has not been infered", TypeKind::<D>::Ref(*rid))
+            }
+            DeclError::SkolemInDeclaration(id) => {
+                write!(f, "rigid type {} can not escape its scope", id)
+            }
+            DeclError::HoleInDeclaration => "`_` may only be used in signatures".fmt(f),
+            DeclError::DuplicateField(name) => {
+                write!(f, "field {} is declared more than once", name)
+            }
+        }
+    }
+}
+
+impl<D: TypeData> fmt::Display for DeclWarning<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeclWarning::UnusedGeneric(gid) => write!(f, "generic {} is never used", gid),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use super::*;
-use crate::{infer::Lift, TypeVisitor};
+use crate::{infer::Lift, kind::KindInference, TypeVisitor};
 use itertools::Itertools;
+use std::borrow::Cow;
 use std::fmt;
 
 #[derive(Debug, Clone)]
@@ -43,6 +44,30 @@ impl<D: TypeData> Function<D> {
             generics,
         }
     }
+
+    /// Generalize a local binding relative to its enclosing environment.
+    ///
+    /// Only the type references which aren't reachable from `monomorphic` are lifted into
+    /// generics, the rest are kept as references shared with the environment.
+    pub fn generalize(
+        &self,
+        tenv: &TEnv<D>,
+        mut generics: Generics<D>,
+        monomorphic: &[RefID],
+    ) -> ForeignFunction<D> {
+        let ptypes = tenv.concretify_types(&self.ptypes);
+        let returns = tenv.concretify_type(&self.returns);
+
+        let mut lifter = Lift::new(tenv, &mut generics).monomorphic(monomorphic);
+
+        ForeignFunction {
+            function: Function {
+                ptypes: lifter.types(ptypes),
+                returns: lifter.type_(returns),
+            },
+            generics,
+        }
+    }
 }
 
 impl<D: TypeData> ForeignFunction<D> {
@@ -51,12 +76,96 @@ impl<D: TypeData> ForeignFunction<D> {
         self
     }
 
-    pub fn instantiate(&self, tenv: &mut TEnv<D>) -> InstantiatedFunction<D> {
-        let mapping = self.generics.to_mapping(tenv);
-        InstantiatedFunction {
-            mapping,
-            function: &self.function,
+    /// Infer the kinds of the generics from how they're applied in the signature.
+    ///
+    /// Explicitly annotated kinds are respected, the rest default to `*` if unconstrained.
+    pub fn infer_kinds(
+        &self,
+        lookup: &dyn KindLookup<D>,
+    ) -> Result<Vec<(D::Generic, Kind)>, KindError<D>> {
+        let mut inference = KindInference::new(lookup);
+        inference.declare(&self.generics);
+        self.constrain_kinds(&mut inference)?;
+        Ok(inference.kinds(&self.generics))
+    }
+
+    /// Infer the kinds of the generics once for the declaration, so that annotations of its
+    /// instantiations are checked against them.
+    pub fn with_kinds(mut self, lookup: &dyn KindLookup<D>) -> Result<Self, KindError<D>> {
+        for (gid, kind) in self.infer_kinds(lookup)? {
+            self.generics.set_kind(gid, kind);
         }
+        Ok(self)
+    }
+
+    pub(crate) fn constrain_kinds(
+        &self,
+        inference: &mut KindInference<D>,
+    ) -> Result<(), KindError<D>> {
+        inference.constraints(&self.generics)?;
+        for t in self.function.ptypes.iter() {
+            inference.fully_applied(t)?;
+        }
+        inference.fully_applied(&self.function.returns)
+    }
+
+    /// Enter the scope of the signature with its generics kept rigid, for checking a body against
+    /// its declaration.
+    ///
+    /// Unlike an instantiation, the generics will only unify with themselves, may not escape into
+    /// references from outer scopes and are assumed to satisfy their constraints. The scope should
+    /// be left with [`TEnv::leave_scope`] once the body has been checked.
+    pub fn skolemize(&self, tenv: &mut TEnv<D>) -> Function<D> {
+        self.skolemize_scoped(tenv).1
+    }
+
+    /// Like [`Self::skolemize`] but also returns the scope substituting the rigid generics, for
+    /// types declared alongside the signature such as its opaque return types.
+    pub fn skolemize_scoped(&self, tenv: &mut TEnv<D>) -> (Scope<D>, Function<D>) {
+        let scope = tenv.enter_scope(&self.generics);
+        let function = Function {
+            ptypes: scope.apply_types(&self.function.ptypes),
+            returns: scope.apply_type(&self.function.returns),
+        };
+        (scope, function)
+    }
+
+    /// Instantiate the signature with fresh references for its generics
+    ///
+    /// Generics without a kind from [`Self::with_kinds`] have it infered from the signature, so
+    /// that annotations are kind checked either way. An ill-kinded signature is reported by
+    /// [`Self::with_kinds`], its generics are left unkinded here.
+    pub fn instantiate<'s>(&'s self, tenv: &mut TEnv<D>) -> InstantiatedFunction<'s, D> {
+        let mut mapping = self.generics.to_mapping(tenv);
+
+        let unkinded = self
+            .generics
+            .iter()
+            .any(|(gid, _)| self.generics.kind(gid).is_none());
+        if unkinded {
+            for (gid, kind) in self.infer_kinds(&()).unwrap_or_default() {
+                if self.generics.kind(&gid).is_none() {
+                    mapping.set_kind(gid, kind);
+                }
+            }
+        }
+
+        // Each instantiation infers its own types for the holes of the signature
+        let function = if self.function.has_holes() {
+            Cow::Owned(Function {
+                ptypes: self
+                    .function
+                    .ptypes
+                    .iter()
+                    .map(|t| tenv.spawn_holes(t))
+                    .collect(),
+                returns: tenv.spawn_holes(&self.function.returns),
+            })
+        } else {
+            Cow::Borrowed(&self.function)
+        };
+
+        InstantiatedFunction { mapping, function }
     }
 }
 
@@ -64,11 +173,19 @@ impl<D: TypeData> Function<D> {
     pub fn new(ptypes: TypesBuf<D>, returns: Type<D>) -> Self {
         Function { ptypes, returns }
     }
+
+    fn has_holes(&self) -> bool {
+        fn has_hole<D: TypeData>(t: &Type<D>) -> bool {
+            matches!(t.constr, TypeKind::Hole) || t.params.iter().any(has_hole)
+        }
+
+        self.ptypes.iter().chain([&self.returns]).any(has_hole)
+    }
 }
 
 pub struct InstantiatedFunction<'a, D: TypeData> {
     mapping: Mapping<D>,
-    function: &'a Function<D>,
+    function: Cow<'a, Function<D>>,
 }
 
 impl<'a, D: TypeData> InstantiatedFunction<'a, D> {
@@ -88,18 +205,19 @@ impl<D: TypeData> Function<D> {
         &self,
         tenv: &mut TEnv<D>,
         traits: &TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
         params: &Types<D>,
     ) -> Result<(), CallError<D>> {
         let expected = &self.ptypes;
 
         let got = params.len();
-        let exp = params.len();
+        let exp = expected.len();
 
         if got != exp {
             return Err(CallError::ParamCount { got, exp });
         }
 
-        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive).with_kinds(kinds);
         let mut errors = Vec::new();
         for pid in 0..got {
             if let Err(err) = tctx.check(&params[pid], &expected[pid]) {
@@ -0,0 +1,163 @@
+use super::*;
+use crate::{infer::Lift, TypeVisitor};
+
+/// A strongly connected group of mutually recursive functions being infered together
+#[derive(Debug, Clone)]
+pub struct RecursiveGroup<D: TypeData> {
+    members: Vec<Signature<D>>,
+}
+
+/// A function of a recursive group, either with its signature declared or to be infered
+#[derive(Debug, Clone)]
+pub enum GroupMember<D: TypeData> {
+    Inferred { arity: usize },
+    Declared(ForeignFunction<D>),
+}
+
+#[derive(Debug, Clone)]
+enum Signature<D: TypeData> {
+    Inferred(Function<D>),
+    Declared(ForeignFunction<D>),
+}
+
+impl<D: TypeData> RecursiveGroup<D> {
+    /// Spawn monomorphic signatures for functions of the given arities.
+    ///
+    /// The signatures are used as-is for recursive calls while checking the bodies, and are
+    /// only generalized once the whole group has been checked.
+    pub fn spawn(tenv: &mut TEnv<D>, arities: &[usize], meta: D::Meta) -> Self {
+        let members = arities
+            .iter()
+            .map(|&arity| GroupMember::Inferred { arity })
+            .collect();
+        Self::new(tenv, members, meta)
+    }
+
+    /// Functions with declared signatures have their bodies checked against the skolemized
+    /// signature, and are instantiated freshly for each recursive call. This allows polymorphic
+    /// recursion, which can't be infered.
+    pub fn new(tenv: &mut TEnv<D>, members: Vec<GroupMember<D>>, meta: D::Meta) -> Self {
+        let members = members
+            .into_iter()
+            .map(|member| match member {
+                GroupMember::Inferred { arity } => {
+                    let ptypes = tenv.spawn_types(arity, |_| meta.clone());
+                    Signature::Inferred(Function::new(ptypes, tenv.spawn_type(meta.clone())))
+                }
+                GroupMember::Declared(scheme) => Signature::Declared(scheme),
+            })
+            .collect();
+
+        Self { members }
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Enter the scope of the function's body, returning the signature to check it against.
+    ///
+    /// The scope should be left with [`TEnv::leave_scope`] once the body has been checked.
+    pub fn enter(&self, tenv: &mut TEnv<D>, fid: usize) -> Function<D> {
+        match &self.members[fid] {
+            Signature::Inferred(f) => {
+                tenv.enter_scope(&Generics::new());
+                f.clone()
+            }
+            Signature::Declared(scheme) => scheme.skolemize(tenv),
+        }
+    }
+
+    /// The signature to use for a call to the function from within the group
+    pub fn call(&self, tenv: &mut TEnv<D>, fid: usize) -> Function<D> {
+        match &self.members[fid] {
+            Signature::Inferred(f) => f.clone(),
+            Signature::Declared(scheme) => scheme.instantiate(tenv).function(),
+        }
+    }
+
+    /// Jointly lift the infered signatures of the group, declared signatures are kept as-is.
+    ///
+    /// A type reference shared between functions is lifted into the same generic in all of them,
+    /// and each function only declares the generics (and their constraints) it actually uses.
+    pub fn generalize(&self, tenv: &TEnv<D>, monomorphic: &[RefID]) -> Vec<ForeignFunction<D>> {
+        let mut shared = Generics::new();
+
+        let mut lifted = {
+            let mut lifter = Lift::new(tenv, &mut shared).monomorphic(monomorphic);
+            self.members
+                .iter()
+                .filter_map(|member| match member {
+                    Signature::Inferred(f) => Some(Function {
+                        ptypes: lifter.types(f.ptypes.clone()),
+                        returns: lifter.type_(f.returns.clone()),
+                    }),
+                    Signature::Declared(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+
+        self.members
+            .iter()
+            .map(|member| match member {
+                Signature::Declared(scheme) => scheme.clone(),
+                Signature::Inferred(_) => {
+                    let function = lifted.next().unwrap();
+                    let used = used_generics(&function, &shared);
+
+                    let mut generics = Generics::new();
+                    for (gid, constrs) in shared.iter() {
+                        if used.contains(gid) {
+                            generics.insert_with_con(gid.clone(), constrs.clone());
+                        }
+                    }
+
+                    ForeignFunction { generics, function }
+                }
+            })
+            .collect()
+    }
+}
+
+/// The generics used in the function's signature, including the ones only used in the
+/// constraints of those generics.
+fn used_generics<D: TypeData>(function: &Function<D>, shared: &Generics<D>) -> Vec<D::Generic> {
+    let mut used = vec![];
+    let collect = |used: &mut Vec<D::Generic>, constr: &TypeKind<D>| {
+        if let TypeKind::Generic(gid) = constr {
+            if !used.contains(gid) {
+                used.push(gid.clone());
+            }
+        }
+    };
+
+    for t in function
+        .ptypes
+        .iter()
+        .chain(std::iter::once(&function.returns))
+    {
+        t.map_free_constr(&mut |constr| {
+            collect(&mut used, constr);
+            constr.clone()
+        });
+    }
+
+    let mut i = 0;
+    while i < used.len() {
+        let constrs = shared.try_constraints(&used[i]).unwrap_or(&[]);
+        for con in constrs {
+            con.map_constr(|_, constr| {
+                collect(&mut used, constr);
+                constr.clone()
+            });
+        }
+        i += 1;
+    }
+
+    used
+}
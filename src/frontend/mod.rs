@@ -1,13 +1,23 @@
 use crate::*;
 
+mod decl;
 mod function;
+mod group;
+mod opaque;
 mod product;
 mod sum;
 mod r#trait;
 
-pub use function::{ForeignFunction, Function};
-pub use product::{ForeignProduct, InstantiatedProduct, Product};
-pub use r#trait::{ForeignTrait, InstantiatedTrait};
+pub(crate) use decl::DeclValidator;
+pub use decl::{DeclError, DeclWarning, Validated};
+pub use function::{CallError, ForeignFunction, Function};
+pub use group::{GroupMember, RecursiveGroup};
+pub use opaque::ForeignOpaque;
+pub use product::{ForeignProduct, InstantiatedProduct, Product, UnknownField};
+pub use r#trait::{
+    ForeignTrait, ImplFailure, ImplMethod, ImplSkeleton, InstantiatedTrait, MethodCoverage,
+    TraitKinds, TraitMethod,
+};
 pub use sum::{ForeignSum, InstantiatedSum, Sum};
 
 #[macro_export]
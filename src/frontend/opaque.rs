@@ -0,0 +1,103 @@
+use super::*;
+use crate::decls::substitute;
+
+/// An existential return type such as `impl Iterator`.
+///
+/// Callers only see the opaque type through its declared constraints, while the defining
+/// function infers the hidden type which can later be revealed for codegen.
+#[derive(Debug, Clone)]
+pub struct ForeignOpaque<D: TypeData> {
+    pub id: OpaqueID,
+    pub generics: Generics<D>,
+    pub constraints: Vec<Constraint<D>>,
+    hidden: Option<Type<D>>,
+}
+
+impl<D: TypeData> ForeignOpaque<D> {
+    /// Declare an opaque type over the generics of its defining function
+    pub fn declare(
+        traits: &mut TraitIndex<D>,
+        generics: Generics<D>,
+        constraints: Vec<Constraint<D>>,
+    ) -> Self {
+        let id = traits.implement_opaque(generics.clone(), constraints.clone());
+        Self {
+            id,
+            generics,
+            constraints,
+            hidden: None,
+        }
+    }
+
+    /// The opaque type as seen by callers, for use in the defining function's signature
+    pub fn to_type(&self, meta: D::Meta) -> Type<D> {
+        let params = self
+            .generics
+            .iter()
+            .map(|(gid, _)| Type::generic(meta.clone(), gid.clone(), vec![]))
+            .collect();
+
+        Type {
+            constr: TypeKind::Opaque(self.id),
+            meta,
+            params,
+        }
+    }
+
+    /// Spawn the type to check the defining function's returned values against.
+    ///
+    /// The defining function's generics are expected to be skolemized by `scope`, which is
+    /// applied to the declared constraints as well.
+    pub fn spawn_hidden(&self, tenv: &mut TEnv<D>, scope: &Scope<D>, meta: D::Meta) -> Type<D> {
+        let constraints = self
+            .constraints
+            .iter()
+            .map(|con| con.map_constr(|_, constr| scope.apply(constr)))
+            .collect();
+        let rid = tenv.spawn_with_cons(constraints);
+        Type::reference(meta, rid, vec![])
+    }
+
+    /// Record the hidden type once the defining function has been checked.
+    ///
+    /// Fails with the partially infered type if it wasn't fully infered.
+    pub fn define(&mut self, tenv: &TEnv<D>, hidden: &Type<D>) -> Result<(), Type<D>> {
+        // The rigid types of the defining function are stored as the generics they stand in for
+        let hidden = tenv
+            .concretify_type(hidden)
+            .map_free_constr(&mut |constr| match constr {
+                TypeKind::Skolem(id) => TypeKind::Generic(tenv.rigid_generic(*id).clone()),
+                other => other.clone(),
+            });
+        if tenv.is_resolved(&hidden) {
+            self.hidden = Some(hidden);
+            Ok(())
+        } else {
+            Err(hidden)
+        }
+    }
+
+    pub fn hidden(&self) -> Option<&Type<D>> {
+        self.hidden.as_ref()
+    }
+
+    /// Replace any usage of this opaque type with its hidden type
+    pub fn reveal(&self, t: &Type<D>) -> Type<D> {
+        let hidden = self
+            .hidden
+            .as_ref()
+            .expect("reveal called on undefined opaque type");
+
+        t.map_type(&mut |meta, constr, params| match constr {
+            TypeKind::Opaque(id) if *id == self.id => {
+                substitute(&self.generics, &Generics::new(), &params, hidden)
+                    .expect("opaque type applied to fewer parameters than its generics")
+            }
+            _ => Type {
+                meta,
+                constr: constr.clone(),
+                params,
+            },
+        })
+    }
+}
@@ -4,18 +4,46 @@ use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct Product<D: TypeData> {
-    identifier: D::Concrete,
-    fields: TypesBuf<D>,
+    pub identifier: D::Concrete,
+    fields: Vec<(D::Field, Type<D>)>,
 }
 
 impl<D: TypeData> Product<D> {
-    pub fn new(identifier: D::Concrete, fields: TypesBuf<D>) -> Self {
+    pub fn new(identifier: D::Concrete, fields: Vec<(D::Field, Type<D>)>) -> Self {
         Self { identifier, fields }
     }
 
-    pub fn to_foreign(self, generics: Generics<D>) -> ForeignProduct<D> {
-        // TODO: perform validation that we cannot use any non-existant generics
-        ForeignProduct { generics, product: self }
+    fn get(&self, name: &D::Field) -> Option<&Type<D>> {
+        self.fields
+            .iter()
+            .find_map(|(n, t)| if n == name { Some(t) } else { None })
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &D::Field> {
+        self.fields.iter().map(|(name, _)| name)
+    }
+
+    pub fn field_types(&self) -> impl Iterator<Item = &Type<D>> {
+        self.fields.iter().map(|(_, t)| t)
+    }
+
+    pub fn to_foreign(self, generics: Generics<D>) -> Validated<ForeignProduct<D>, D> {
+        let mut validator = DeclValidator::new(&generics);
+        for (i, (name, t)) in self.fields.iter().enumerate() {
+            if self.fields[..i].iter().any(|(n, _)| n == name) {
+                validator.duplicate_field(name.clone());
+            }
+            validator.type_(t, &Generics::new());
+        }
+        let warnings = validator.finish()?;
+
+        Ok((
+            ForeignProduct {
+                generics,
+                product: self,
+            },
+            warnings,
+        ))
     }
 }
 
@@ -41,19 +69,47 @@ pub struct InstantiatedProduct<'a, D: TypeData> {
 }
 
 impl<'a, D: TypeData> InstantiatedProduct<'a, D> {
-    pub fn field(&self, field: usize) -> Type<D> {
-        self.mapping.apply_type(
-            self.product
-                .fields
-                .get(field)
-                .expect("field does not exist"),
-        )
+    pub fn field(&self, name: &D::Field) -> Option<Type<D>> {
+        self.product.get(name).map(|t| self.mapping.apply_type(t))
+    }
+
+    /// `product -> field`
+    pub fn getter(
+        &self,
+        ret_meta: D::Meta,
+        name: &D::Field,
+    ) -> Result<Function<D>, UnknownField<D>> {
+        let field = self.field(name).ok_or_else(|| self.unknown(name))?;
+        Ok(Function::new(vec![self.to_type(ret_meta)], field))
     }
 
-    pub fn accessor(&self, ret_meta: D::Meta, field: usize) -> Function<D> {
-        let params = vec![self.field(field)];
-        let returns = self.to_type(ret_meta);
-        Function::new(params, returns)
+    /// `product, field -> product`
+    pub fn setter(
+        &self,
+        ret_meta: D::Meta,
+        name: &D::Field,
+    ) -> Result<Function<D>, UnknownField<D>> {
+        let field = self.field(name).ok_or_else(|| self.unknown(name))?;
+        let product = self.to_type(ret_meta);
+        Ok(Function::new(vec![product.clone(), field], product))
+    }
+
+    /// `fields.. -> product`, with the parameters in the order the fields were declared
+    pub fn constructor(&self, ret_meta: D::Meta) -> Function<D> {
+        let params = self
+            .product
+            .fields
+            .iter()
+            .map(|(_, t)| self.mapping.apply_type(t))
+            .collect();
+        Function::new(params, self.to_type(ret_meta))
+    }
+
+    fn unknown(&self, name: &D::Field) -> UnknownField<D> {
+        UnknownField {
+            product: self.product.identifier.clone(),
+            field: name.clone(),
+        }
     }
 
     pub fn mapping_mut(&mut self) -> &mut Mapping<D> {
@@ -63,6 +119,19 @@ impl<'a, D: TypeData> InstantiatedProduct<'a, D> {
 
 impl_to_type!(InstantiatedProduct<'a, D>, product);
 
+/// A field was accessed which the product type does not have
+#[derive(Clone, Debug)]
+pub struct UnknownField<D: TypeData> {
+    pub product: D::Concrete,
+    pub field: D::Field,
+}
+
+impl<D: TypeData> fmt::Display for UnknownField<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} has no field named {}", self.product, self.field)
+    }
+}
+
 impl<D: TypeData> fmt::Display for ForeignProduct<D> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.generics.is_empty() {
@@ -79,7 +148,9 @@ impl<D: TypeData> fmt::Display for Product<D> {
             f,
             "{{ {} . {} }}",
             self.identifier,
-            self.fields.iter().format(", ")
+            self.fields
+                .iter()
+                .format_with(", ", |(name, t), f| f(&format_args!("{}: {}", name, t)))
         )
     }
 }
@@ -5,16 +5,58 @@ use std::fmt;
 #[derive(Debug, Clone)]
 pub struct Sum<D: TypeData> {
     pub identifier: D::Concrete,
-    pub variants: Vec<TypesBuf<D>>,
+    variants: Vec<TypesBuf<D>>,
+    /// Generics local to each variant, hidden once the variant is constructed
+    existentials: Vec<Generics<D>>,
 }
 
 impl<D: TypeData> Sum<D> {
     pub fn new(identifier: D::Concrete, variants: Vec<TypesBuf<D>>) -> Self {
-        Self { identifier, variants }
+        let existentials = variants.iter().map(|_| Generics::new()).collect();
+        Self {
+            identifier,
+            variants,
+            existentials,
+        }
+    }
+
+    /// Declare existential generics for a variant, such as the `a` of `∃a is Show. _ a`
+    pub fn with_existential(mut self, variant: usize, generics: Generics<D>) -> Self {
+        assert!(
+            variant < self.variants.len(),
+            "existentials declared for a variant which does not exist"
+        );
+        self.existentials[variant] = generics;
+        self
+    }
+
+    /// The parameters of each variant
+    pub fn variants(&self) -> &[TypesBuf<D>] {
+        &self.variants
     }
 
-    pub fn to_foreign(self, generics: Generics<D>) -> ForeignSum<D> {
-        ForeignSum { generics, sum: self }
+    /// The existential generics of each variant, indexed like [`Self::variants`]
+    pub fn existentials(&self) -> &[Generics<D>] {
+        &self.existentials
+    }
+
+    pub fn to_foreign(self, generics: Generics<D>) -> Validated<ForeignSum<D>, D> {
+        let mut validator = DeclValidator::new(&generics);
+        for (params, existentials) in self.variants.iter().zip(&self.existentials) {
+            validator.local_generics(existentials);
+            for t in params {
+                validator.type_(t, existentials);
+            }
+        }
+        let warnings = validator.finish()?;
+
+        Ok((
+            ForeignSum {
+                generics,
+                sum: self,
+            },
+            warnings,
+        ))
     }
 }
 
@@ -37,23 +79,61 @@ impl<D: TypeData> ForeignSum<D> {
 }
 
 impl<'a, D: TypeData> InstantiatedSum<'a, D> {
+    /// The parameters of a variant, with its existential generics left as-is
     pub fn variant(&self, variant: usize) -> TypesBuf<D> {
-        let variant = self
+        let params = self
             .sum
             .variants
             .get(variant)
             .expect("variant does not exist");
 
-        self.mapping.apply_types(variant)
+        params
+            .iter()
+            .map(|t| self.apply_variant(variant, t))
+            .collect()
     }
 
-    pub fn constructor(&self, ret_meta: D::Meta, variant: usize) -> Function<D> {
-        let params = self.variant(variant);
+    fn apply_variant(&self, variant: usize, t: &Type<D>) -> Type<D> {
+        t.map_free_constr(&mut |constr| self.apply_constr(variant, constr))
+    }
+
+    fn apply_constr(&self, variant: usize, constr: &TypeKind<D>) -> TypeKind<D> {
+        match constr {
+            TypeKind::Generic(gid) if self.sum.existentials[variant].contains(gid.clone()) => {
+                constr.clone()
+            }
+            _ => self.mapping.apply(constr),
+        }
+    }
+
+    /// Constructing a variant instantiates its existential generics as fresh references, which
+    /// are then hidden by the returned type.
+    pub fn constructor(
+        &self,
+        tenv: &mut TEnv<D>,
+        ret_meta: D::Meta,
+        variant: usize,
+    ) -> Function<D> {
+        let mut mapping = self.mapping.clone();
+        self.sum.existentials[variant].append_to_mapping(tenv, &mut mapping);
+
+        let params = mapping.apply_types(&self.sum.variants[variant]);
         let returns = self.to_type(ret_meta);
 
         Function::new(params, returns)
     }
 
+    /// Destructure a variant when pattern matching on it.
+    ///
+    /// This enters a scope in which the existential generics are rigid and assumed to satisfy
+    /// their constraints. It should be left with [`TEnv::leave_scope`] after the match arm.
+    pub fn destruct(&self, tenv: &mut TEnv<D>, variant: usize) -> TypesBuf<D> {
+        let existentials = self.sum.existentials[variant]
+            .map_constr(|_, constr| self.apply_constr(variant, constr));
+        let scope = tenv.enter_scope(&existentials);
+        scope.apply_types(&self.variant(variant))
+    }
+
     pub fn mapping_mut(&mut self) -> &mut Mapping<D> {
         &mut self.mapping
     }
@@ -77,12 +157,20 @@ impl<D: TypeData> fmt::Display for Sum<D> {
             f,
             "{} = {}",
             self.identifier,
-            self.variants
-                .iter()
-                .format_with(" | ", |params, f| f(&format_args!(
-                    "_ {}",
-                    params.iter().format(" ")
-                )))
+            self.variants.iter().zip(&self.existentials).format_with(
+                " | ",
+                |(params, existentials), f| {
+                    if existentials.is_empty() {
+                        f(&format_args!("_ {}", params.iter().format(" ")))
+                    } else {
+                        f(&format_args!(
+                            "∃{}. _ {}",
+                            existentials,
+                            params.iter().format(" ")
+                        ))
+                    }
+                }
+            )
         )
     }
 }
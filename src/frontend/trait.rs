@@ -1,14 +1,89 @@
 use super::*;
-use crate::mapping::{ImplHeaderFailure, ImplToTrait};
+use crate::decls::substitute;
+use crate::kind::KindInference;
+use crate::mapping::{AnnotationError, ImplHeaderFailure, ImplToTrait};
+use crate::TypeVisitor;
 
 #[derive(Debug, Clone)]
 pub struct ForeignTrait<D: TypeData> {
     pub identifier: D::Trait,
     pub generics: Generics<D>,
-    methods: Vec<ForeignFunction<D>>,
+    methods: Vec<TraitMethod<D>>,
     // TODO: associated types
 }
 
+/// The infered kinds of a trait declaration
+#[derive(Debug, Clone)]
+pub struct TraitKinds<D: TypeData> {
+    /// The kind of the types implementing this trait
+    pub self_: Kind,
+    pub generics: Vec<(D::Generic, Kind)>,
+    /// The kinds of each method's own generics
+    pub methods: Vec<Vec<(D::Generic, Kind)>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TraitMethod<D: TypeData> {
+    pub name: D::Method,
+    pub signature: ForeignFunction<D>,
+    /// The body used for implementations which don't provide this method themselves
+    pub default: Option<D::Function>,
+}
+
+/// Which of a trait's methods an implementation leaves out
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MethodCoverage {
+    /// Methods without a default that the implementation has to provide
+    pub missing: Vec<usize>,
+    /// Methods that fall back to the trait's default
+    pub defaulted: Vec<usize>,
+}
+
+impl MethodCoverage {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// The method signatures expected from an implementation, see [`ForeignTrait::generate_impl`]
+pub type ImplSkeleton<D> = Vec<(<D as TypeData>::Method, ForeignFunction<D>)>;
+
+/// A method provided by an implementation block
+#[derive(Debug, Clone)]
+pub struct ImplMethod<D: TypeData> {
+    pub name: D::Method,
+    pub function: D::Function,
+    pub signature: ForeignFunction<D>,
+}
+
+/// Why an implementation block doesn't match its trait
+#[derive(Debug, Clone)]
+pub enum ImplFailure<D: TypeData> {
+    /// A method without a default was not provided
+    Missing(D::Method),
+    /// A method not declared by the trait was provided
+    Unknown(D::Method),
+    /// The same method was provided more than once
+    Duplicate(D::Method),
+    TraitParamCount {
+        got: usize,
+        exp: usize,
+    },
+    /// The trait parameters do not satisfy the trait declaration's generics
+    TraitParams(AnnotationError<D>),
+    Arity {
+        method: D::Method,
+        got: usize,
+        exp: usize,
+    },
+    GenericCount {
+        method: D::Method,
+        got: usize,
+        exp: usize,
+    },
+    Header(D::Method, Vec<ImplHeaderFailure<D>>),
+}
+
 impl<D: TypeData> ForeignTrait<D> {
     pub fn new(identifier: D::Trait, generics: Generics<D>) -> Self {
         if !generics.iter().all(|(_, constr)| constr.is_empty()) {
@@ -27,8 +102,287 @@ impl<D: TypeData> ForeignTrait<D> {
         }
     }
 
-    pub fn push_method(&mut self, method: ForeignFunction<D>) {
-        self.methods.push(method);
+    pub fn push_method(&mut self, name: D::Method, signature: ForeignFunction<D>) {
+        self.methods.push(TraitMethod {
+            name,
+            signature,
+            default: None,
+        });
+    }
+
+    /// Declare a method which implementations may omit, in which case `default` is used
+    pub fn push_default_method(
+        &mut self,
+        name: D::Method,
+        signature: ForeignFunction<D>,
+        default: D::Function,
+    ) {
+        self.methods.push(TraitMethod {
+            name,
+            signature,
+            default: Some(default),
+        });
+    }
+
+    /// Find the index of the method declared with this name
+    pub fn method_position(&self, name: &D::Method) -> Option<usize> {
+        self.methods.iter().position(|m| m.name == *name)
+    }
+
+    pub fn methods(&self) -> &[TraitMethod<D>] {
+        &self.methods
+    }
+
+    /// Check which methods are left out by an implementation's method table
+    pub fn coverage(&self, table: &MethodTable<D>) -> MethodCoverage {
+        let mut coverage = MethodCoverage::default();
+
+        for (mid, method) in self.methods.iter().enumerate() {
+            if let Some(Some(_)) = table.get(mid) {
+                continue;
+            }
+
+            if method.default.is_some() {
+                coverage.defaulted.push(mid);
+            } else {
+                coverage.missing.push(mid);
+            }
+        }
+
+        coverage
+    }
+
+    /// Validate an entire implementation block of this trait
+    ///
+    /// If every method matches the trait the implementation is registered into `traits`, the
+    /// trait parameters are kind checked against `kinds` and checked to be well-formed against
+    /// `decls`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn implement(
+        &self,
+        traits: &mut TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
+        decls: &TypeDecls<D>,
+        generics: Generics<D>,
+        trait_params: TypesBuf<D>,
+        impltor: Type<D>,
+        methods: Vec<ImplMethod<D>>,
+    ) -> Result<ImplID, Vec<ImplFailure<D>>> {
+        let exp = self.generics.len();
+        if trait_params.len() != exp {
+            let got = trait_params.len();
+            return Err(vec![ImplFailure::TraitParamCount { got, exp }]);
+        }
+
+        let mut tenv = TEnv::new();
+        let mut inst = self.instantiate(&mut tenv);
+        if let Err(err) =
+            inst.mapping_mut()
+                .annotate_types(&mut tenv, traits, kinds, decls, &trait_params)
+        {
+            return Err(vec![match err {
+                AnnotationError::ParamCount { got, exp } => {
+                    ImplFailure::TraitParamCount { got, exp }
+                }
+                err => ImplFailure::TraitParams(err),
+            }]);
+        }
+        inst.set_self(impltor.clone(), &mut tenv);
+
+        let mut failures = vec![];
+        let mut table: MethodTable<D> = vec![None; self.methods.len()];
+        let mut signatures = vec![None; self.methods.len()];
+
+        for ImplMethod {
+            name,
+            function,
+            signature,
+        } in methods
+        {
+            let mid = match self.method_position(&name) {
+                Some(mid) => mid,
+                None => {
+                    failures.push(ImplFailure::Unknown(name));
+                    continue;
+                }
+            };
+
+            if table[mid].is_some() {
+                failures.push(ImplFailure::Duplicate(name));
+                continue;
+            }
+            table[mid] = Some(function);
+
+            let expected = &self.methods[mid].signature;
+
+            let (got, exp) = (
+                signature.function.ptypes.len(),
+                expected.function.ptypes.len(),
+            );
+            if got != exp {
+                failures.push(ImplFailure::Arity {
+                    method: name,
+                    got,
+                    exp,
+                });
+                continue;
+            }
+
+            let (got, exp) = (signature.generics.len(), expected.generics.len());
+            if got != exp {
+                failures.push(ImplFailure::GenericCount {
+                    method: name,
+                    got,
+                    exp,
+                });
+                continue;
+            }
+
+            let (signature, errors) = inst.verify_method_annotation(mid, signature, &tenv);
+            if errors.is_empty() {
+                signatures[mid] = Some(signature);
+            } else {
+                failures.push(ImplFailure::Header(name, errors));
+            }
+        }
+
+        for mid in self.coverage(&table).missing {
+            failures.push(ImplFailure::Missing(self.methods[mid].name.clone()));
+        }
+
+        if failures.is_empty() {
+            Ok(traits.implement_with_signatures(
+                generics,
+                self.identifier.clone(),
+                trait_params,
+                impltor,
+                table,
+                signatures,
+            ))
+        } else {
+            Err(failures)
+        }
+    }
+
+    /// Generate the method signatures expected from an implementation of this trait
+    ///
+    /// `self` and the trait's generics are substituted with `impltor` and `trait_params`, while
+    /// generics declared by the methods themselves are renamed to not clash with the generics of
+    /// the implementation block.
+    pub fn generate_impl(
+        &self,
+        generics: &Generics<D>,
+        trait_params: &Types<D>,
+        impltor: &Type<D>,
+    ) -> Result<ImplSkeleton<D>, ImplFailure<D>> {
+        if self.generics.len() != trait_params.len() {
+            return Err(ImplFailure::TraitParamCount {
+                got: trait_params.len(),
+                exp: self.generics.len(),
+            });
+        }
+
+        let with_impltor = |meta, constr: &TypeKind<D>, params: TypesBuf<D>| match constr {
+            TypeKind::Self_ => {
+                let mut t = impltor.clone();
+                t.params.extend(params);
+                t
+            }
+            _ => Type {
+                meta,
+                constr: constr.clone(),
+                params,
+            },
+        };
+
+        Ok(self
+            .methods
+            .iter()
+            .map(|method| {
+                let signature = &method.signature;
+
+                let mut taken = generics.clone();
+                let renamed = signature
+                    .generics
+                    .iter()
+                    .map(|(gid, _)| {
+                        let new = if taken.contains(gid.clone()) {
+                            D::first_available(&taken)
+                        } else {
+                            gid.clone()
+                        };
+                        taken.insert(new.clone());
+                        new
+                    })
+                    .collect::<Vec<_>>();
+
+                // The method's own generics shadow the trait's, so they're declared first
+                let mut declared = signature.generics.clone();
+                declared.extend(&self.generics);
+                let params = renamed
+                    .iter()
+                    .map(|new| Type::generic(impltor.meta.clone(), new.clone(), vec![]))
+                    .chain(trait_params.iter().cloned())
+                    .collect::<Vec<_>>();
+
+                let local = Generics::new();
+                let mut mgenerics = Generics::new();
+                for ((gid, constrs), new) in signature.generics.iter().zip(renamed) {
+                    let constrs = constrs
+                        .iter()
+                        .map(|con| {
+                            substitute(&declared, &local, &params, con)
+                                .expect("every declared generic is given")
+                                .map_types(with_impltor)
+                        })
+                        .collect();
+                    if let Some(kind) = signature.generics.kind(gid) {
+                        mgenerics.set_kind(new.clone(), kind.clone());
+                    }
+                    mgenerics.insert_with_con(new, constrs);
+                }
+
+                let function = substitute(&declared, &local, &params, &signature.function)
+                    .expect("every declared generic is given")
+                    .map_types(with_impltor);
+
+                (
+                    method.name.clone(),
+                    ForeignFunction {
+                        generics: mgenerics,
+                        function,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Infer the kinds of `Self`, the trait's generics and the generics of each method
+    pub fn infer_kinds(&self, lookup: &dyn KindLookup<D>) -> Result<TraitKinds<D>, KindError<D>> {
+        let mut inference = KindInference::new(lookup);
+
+        if let Some(kind) = lookup.trait_(&self.identifier) {
+            inference.declare_self(&kind)?;
+        }
+
+        inference.declare(&self.generics);
+        inference.constraints(&self.generics)?;
+
+        let mut scopes = Vec::with_capacity(self.methods.len());
+        for method in self.methods.iter() {
+            let previous = inference.declare(&method.signature.generics);
+            method.signature.constrain_kinds(&mut inference)?;
+            scopes.push(inference.leave(previous));
+        }
+
+        Ok(TraitKinds {
+            self_: inference.self_kind(),
+            generics: inference.kinds(&self.generics),
+            methods: scopes
+                .into_iter()
+                .map(|scope| inference.scope_kinds(scope))
+                .collect(),
+        })
     }
 
     pub fn instantiate(&self, tenv: &mut TEnv<D>) -> InstantiatedTrait<D> {
@@ -54,7 +408,7 @@ pub struct InstantiatedTrait<'a, D: TypeData> {
     pub trid: D::Trait,
     mapping: Mapping<D>,
     // maybe we should have the self assignment here instead?
-    pub methods: &'a Vec<ForeignFunction<D>>,
+    pub methods: &'a [TraitMethod<D>],
 }
 
 impl<'a, D: TypeData> InstantiatedTrait<'a, D> {
@@ -79,7 +433,7 @@ impl<'a, D: TypeData> InstantiatedTrait<'a, D> {
     pub fn method(&self, mid: usize, tenv: &mut TEnv<D>) -> Function<D> {
         let mut mapping = self.mapping.clone();
 
-        let f = &self.methods[mid];
+        let f = &self.methods[mid].signature;
 
         // there's also generic attached to the function that aren't declared for the trait
         f.generics.append_to_mapping(tenv, &mut mapping);
@@ -100,8 +454,16 @@ impl<'a, D: TypeData> InstantiatedTrait<'a, D> {
         &mut self.mapping
     }
 
+    /// The type parameters given to the trait
+    pub fn trait_params(&self, meta: D::Meta) -> TypesBuf<D> {
+        self.mapping.to_types(meta)
+    }
+
     /// Verify that `method` is a valid declaration of this trait
     ///
+    /// Any inference holes, see [`Type::hole`], left in `method` are filled in from the trait's
+    /// declaration.
+    ///
     /// Returns a valid form of this trait's method
     pub fn verify_method_annotation(
         &self,
@@ -111,9 +473,10 @@ impl<'a, D: TypeData> InstantiatedTrait<'a, D> {
     ) -> (ForeignFunction<D>, Vec<ImplHeaderFailure<D>>) {
         assert!(self.mapping.resolve_self().is_some());
 
-        let expected = &self.methods[mid];
+        let expected = &self.methods[mid].signature;
 
-        let mut converter = ImplToTrait::new(method.generics, &self.mapping, tenv);
+        let mut converter =
+            ImplToTrait::new(method.generics, &self.mapping, tenv).expecting(&expected.generics);
         let ptypes = converter.types(method.function.ptypes, &expected.function.ptypes);
         let returns = converter.type_(method.function.returns, &expected.function.returns);
 
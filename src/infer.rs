@@ -1,4 +1,6 @@
-use crate::{Constraint, Generics, Type, TypeData, TypeKind, TypeVisitor, Types, TypesBuf};
+use crate::{
+    Constraint, Generics, SkolemID, Type, TypeData, TypeKind, TypeVisitor, Types, TypesBuf,
+};
 use itertools::Itertools;
 use owo_colors::OwoColorize;
 use std::fmt;
@@ -14,17 +16,156 @@ pub struct Assignment<D: TypeData> {
 pub struct TEnv<D: TypeData> {
     // we need some way to track associated types as well
     asgn: Vec<TEntry<D>>,
+    level: usize,
+    skolems: Vec<Skolem<D>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct TEntry<D: TypeData> {
     pub assignment: Option<Assignment<D>>,
     pub constraints: Vec<Constraint<D>>,
+    /// The scope this reference lives in, rigid generics of deeper scopes may not escape into it
+    pub level: usize,
+    /// Constraints on partial applications of this reference, which can only be checked once
+    /// the type constructor is known
+    pub deferred: Vec<(Type<D>, Constraint<D>)>,
+}
+
+/// The rigid types standing in for the generics of an entered scope
+#[derive(Clone, Debug)]
+pub struct Scope<D: TypeData> {
+    skolems: Vec<(D::Generic, SkolemID)>,
+}
+
+impl<D: TypeData> Scope<D> {
+    pub fn get(&self, gid: &D::Generic) -> Option<SkolemID> {
+        self.skolems
+            .iter()
+            .find_map(|(g, id)| if g == gid { Some(*id) } else { None })
+    }
+
+    pub fn apply(&self, kind: &TypeKind<D>) -> TypeKind<D> {
+        match kind {
+            TypeKind::Generic(gid) => match self.get(gid) {
+                Some(id) => TypeKind::Skolem(id),
+                None => kind.clone(),
+            },
+            other => other.clone(),
+        }
+    }
+
+    pub fn apply_type(&self, type_: &Type<D>) -> Type<D> {
+        type_.map_free_constr(&mut |constr| self.apply(constr))
+    }
+
+    pub fn apply_types(&self, types: &Types<D>) -> TypesBuf<D> {
+        types.iter().map(|t| self.apply_type(t)).collect()
+    }
+}
+
+/// A rigid type standing in for a generic of a scope being checked
+#[derive(Clone, Debug)]
+pub(crate) struct Skolem<D: TypeData> {
+    pub generic: D::Generic,
+    pub level: usize,
+    /// The constraints which may be assumed to hold for the generic
+    pub assumptions: Vec<Constraint<D>>,
 }
 
 impl<D: TypeData> TEnv<D> {
     pub fn new() -> Self {
-        Self { asgn: vec![] }
+        Self {
+            asgn: vec![],
+            level: 0,
+            skolems: vec![],
+        }
+    }
+
+    /// Enter the scope of a declared signature, treating its generics as rigid
+    ///
+    /// Each generic is given a fresh rigid type which only unifies with itself, may not escape
+    /// into references from outer scopes and is assumed to satisfy the generic's constraints.
+    /// The returned scope substitutes the generics with their rigid types.
+    pub fn enter_scope(&mut self, generics: &Generics<D>) -> Scope<D> {
+        self.level += 1;
+
+        let scope = Scope {
+            skolems: generics
+                .iter()
+                .enumerate()
+                .map(|(i, (gid, _))| (gid.clone(), SkolemID(self.skolems.len() + i)))
+                .collect(),
+        };
+
+        for (gid, constrs) in generics.iter() {
+            let assumptions = constrs
+                .iter()
+                .map(|con| con.map_constr(|_, constr| scope.apply(constr)))
+                .collect();
+
+            self.skolems.push(Skolem {
+                generic: gid.clone(),
+                level: self.level,
+                assumptions,
+            });
+        }
+
+        scope
+    }
+
+    pub fn leave_scope(&mut self) {
+        assert!(
+            self.level > 0,
+            "leave_scope called without a matching enter_scope"
+        );
+        self.level -= 1;
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// The generic a rigid type stands in for
+    pub fn rigid_generic(&self, id: SkolemID) -> &D::Generic {
+        &self.skolem(id).generic
+    }
+
+    pub(crate) fn skolem(&self, id: SkolemID) -> &Skolem<D> {
+        &self.skolems[id.0]
+    }
+
+    /// Move the references of the type out to `level`, since they're about to be reachable from
+    /// a reference of that scope.
+    ///
+    /// Fails with the rigid generic if it would escape its scope.
+    pub(crate) fn lower_to(&mut self, t: &Type<D>, level: usize) -> Result<(), D::Generic> {
+        let mut escaping = None;
+        let mut refs = vec![];
+
+        self.concretify_type(t).map_constr(&mut |_, constr| {
+            match constr {
+                TypeKind::Skolem(id) => {
+                    let skolem = self.skolem(*id);
+                    if skolem.level > level {
+                        escaping = Some(skolem.generic.clone());
+                    }
+                }
+                TypeKind::Ref(rid) => refs.push(*rid),
+                _ => {}
+            }
+            constr.clone()
+        });
+
+        if let Some(gid) = escaping {
+            return Err(gid);
+        }
+
+        for rid in refs {
+            let entry = self.get_mut(rid);
+            entry.level = entry.level.min(level);
+        }
+
+        Ok(())
     }
 
     pub fn is_empty(&self) -> bool {
@@ -40,7 +181,12 @@ impl<D: TypeData> TEnv<D> {
 
     pub fn spawn_with_cons(&mut self, constraints: Vec<Constraint<D>>) -> RefID {
         let rid = self.asgn.len();
-        self.asgn.push(TEntry { assignment: None, constraints });
+        self.asgn.push(TEntry {
+            assignment: None,
+            constraints,
+            level: self.level,
+            deferred: vec![],
+        });
         rid
     }
 
@@ -48,6 +194,14 @@ impl<D: TypeData> TEnv<D> {
         self.spawn_with_cons(vec![])
     }
 
+    /// Replace the inference holes of a declared type with fresh references
+    pub fn spawn_holes(&mut self, t: &Type<D>) -> Type<D> {
+        t.map_free_constr(&mut |constr| match constr {
+            TypeKind::Hole => TypeKind::Ref(self.spawn()),
+            other => other.clone(),
+        })
+    }
+
     pub fn spawn_type(&mut self, meta: D::Meta) -> Type<D> {
         Type::reference(meta, self.spawn(), vec![])
     }
@@ -99,6 +253,57 @@ impl<D: TypeData> TEnv<D> {
         })
     }
 
+    /// Whether the type has been fully infered, leaving no pending type references
+    pub fn is_resolved(&self, t: &Type<D>) -> bool {
+        let mut resolved = true;
+        self.concretify_type(t).map_constr(&mut |_, constr| {
+            if let TypeKind::Ref(_) = constr {
+                resolved = false;
+            }
+            constr.clone()
+        });
+        resolved
+    }
+
+    /// The un-infered type references of a type
+    pub fn free_refs(&self, t: &Type<D>) -> Vec<RefID> {
+        let mut rids = vec![];
+        self.concretify_type(t).map_constr(&mut |_, constr| {
+            if let TypeKind::Ref(rid) = constr {
+                if !rids.contains(rid) {
+                    rids.push(*rid);
+                }
+            }
+            constr.clone()
+        });
+        rids
+    }
+
+    /// All un-infered type references reachable from `roots`, either through their assignments
+    /// or through the parameters of their constraints.
+    pub fn reachable(&self, roots: &[RefID]) -> Vec<RefID> {
+        let mut found = vec![];
+        let mut pending = roots.to_vec();
+
+        while let Some(rid) = pending.pop() {
+            let t = Type::reference(D::Meta::default(), rid, vec![]);
+            for rid in self.free_refs(&t) {
+                if found.contains(&rid) {
+                    continue;
+                }
+                found.push(rid);
+
+                for constr in self.constraints(rid) {
+                    for param in constr.params.iter() {
+                        pending.extend(self.free_refs(param));
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
     pub fn concretify_types(&self, ts: &Types<D>) -> TypesBuf<D> {
         ts.iter().map(|t| self.concretify_type(t)).collect()
     }
@@ -123,6 +328,7 @@ pub struct Lift<'a, D: TypeData> {
     pub(crate) forall: &'a mut Generics<D>,
     tenv: &'a TEnv<D>,
     reverse_conversion: Vec<(RefID, D::Generic)>,
+    monomorphic: Vec<RefID>,
 }
 
 impl<'a, D: TypeData> Lift<'a, D> {
@@ -131,9 +337,19 @@ impl<'a, D: TypeData> Lift<'a, D> {
             tenv,
             forall,
             reverse_conversion: vec![],
+            monomorphic: vec![],
         }
     }
 
+    /// Keep the type references reachable from `rids` as they are instead of generalizing them.
+    ///
+    /// This is used to generalize local bindings, where the types of the enclosing environment
+    /// are still being infered and may not be instantiated differently per usage.
+    pub fn monomorphic(mut self, rids: &[RefID]) -> Self {
+        self.monomorphic = self.tenv.reachable(rids);
+        self
+    }
+
     pub fn type_(&mut self, t: Type<D>) -> Type<D> {
         self.tenv
             .into_concretify_type(t)
@@ -173,6 +389,7 @@ impl<'a, D: TypeData> Lift<'a, D> {
             //
             // So; perhaps we do still want to check the tenv?
             // although; it's more clean to keep it separate.
+            TypeKind::Ref(rid) if self.monomorphic.contains(&rid) => TypeKind::Ref(rid),
             TypeKind::Ref(rid) => TypeKind::Generic(self.generate_gid(rid)),
             other => other,
         }
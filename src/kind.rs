@@ -0,0 +1,390 @@
+use crate::{Constraint, Generics, Type, TypeData, TypeKind};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The kind of a type, describing how many type parameters it needs to be fully applied
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// `*`, a fully applied type
+    Type,
+    /// `k -> k`, a type constructor which needs a parameter of the left kind
+    Arrow(Box<Kind>, Box<Kind>),
+}
+
+impl Kind {
+    /// The kind of a type constructor taking `params` fully applied types
+    pub fn arity(params: usize) -> Kind {
+        (0..params).fold(Kind::Type, |ret, _| {
+            Kind::Arrow(Box::new(Kind::Type), Box::new(ret))
+        })
+    }
+
+    /// Amount of type parameters needed to fully apply a type of this kind
+    pub fn params(&self) -> usize {
+        match self {
+            Kind::Type => 0,
+            Kind::Arrow(_, ret) => 1 + ret.params(),
+        }
+    }
+
+    /// The kind remaining after applying `n` type parameters
+    pub fn apply(&self, n: usize) -> Option<&Kind> {
+        match (n, self) {
+            (0, _) => Some(self),
+            (_, Kind::Arrow(_, ret)) => ret.apply(n - 1),
+            (_, Kind::Type) => None,
+        }
+    }
+}
+
+/// Declared kinds of type constructors and traits
+pub trait KindLookup<D: TypeData> {
+    fn concrete(&self, constr: &D::Concrete) -> Option<Kind>;
+
+    /// The kind of the types implementing this trait
+    fn trait_(&self, _trid: &D::Trait) -> Option<Kind> {
+        None
+    }
+}
+
+/// Nothing is declared, the kinds of all concrete types are infered from their usage
+impl<D: TypeData> KindLookup<D> for () {
+    fn concrete(&self, _: &D::Concrete) -> Option<Kind> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Kinds<D: TypeData> {
+    concrete: HashMap<D::Concrete, Kind>,
+    traits: HashMap<D::Trait, Kind>,
+}
+
+impl<D: TypeData> Kinds<D> {
+    pub fn new() -> Self {
+        Self {
+            concrete: HashMap::new(),
+            traits: HashMap::new(),
+        }
+    }
+
+    pub fn declare_concrete(&mut self, constr: D::Concrete, kind: Kind) {
+        self.concrete.insert(constr, kind);
+    }
+
+    pub fn declare_trait(&mut self, trid: D::Trait, kind: Kind) {
+        self.traits.insert(trid, kind);
+    }
+}
+
+impl<D: TypeData> Default for Kinds<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: TypeData> KindLookup<D> for Kinds<D> {
+    fn concrete(&self, constr: &D::Concrete) -> Option<Kind> {
+        self.concrete.get(constr).cloned()
+    }
+
+    fn trait_(&self, trid: &D::Trait) -> Option<Kind> {
+        self.traits.get(trid).cloned()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum KindError<D: TypeData> {
+    Missmatch {
+        type_: Type<D>,
+        expected: Kind,
+        got: Kind,
+    },
+    /// The type would need an infinitely recursive kind, such as in `(f f)`
+    Infinite(Type<D>),
+}
+
+#[derive(Debug, Clone)]
+enum IKind {
+    Type,
+    Arrow(Box<IKind>, Box<IKind>),
+    Var(usize),
+}
+
+enum UnifyError {
+    Missmatch,
+    Infinite,
+}
+
+pub(crate) struct Scope<D: TypeData>(Vec<(D::Generic, IKind)>);
+
+/// Infers the kinds of generics from how they're applied in a declaration
+pub(crate) struct KindInference<'l, D: TypeData> {
+    lookup: &'l dyn KindLookup<D>,
+    vars: Vec<Option<IKind>>,
+    scope: Vec<(D::Generic, IKind)>,
+    self_: IKind,
+}
+
+impl<'l, D: TypeData> KindInference<'l, D> {
+    pub(crate) fn new(lookup: &'l dyn KindLookup<D>) -> Self {
+        Self {
+            lookup,
+            vars: vec![Option::None],
+            scope: vec![],
+            self_: IKind::Var(0),
+        }
+    }
+
+    fn fresh(&mut self) -> IKind {
+        self.vars.push(None);
+        IKind::Var(self.vars.len() - 1)
+    }
+
+    /// Bring generics into scope, returning the previous scope to later be restored with `leave`
+    pub(crate) fn declare(&mut self, generics: &Generics<D>) -> usize {
+        let previous = self.scope.len();
+        for (gid, _) in generics.iter() {
+            let kind = match generics.kind(gid) {
+                Some(kind) => IKind::from(kind),
+                None => self.fresh(),
+            };
+            self.scope.push((gid.clone(), kind));
+        }
+        previous
+    }
+
+    /// Leave the scope, keeping its generics so their kinds can still be resolved once the
+    /// rest of the declaration has been inferred
+    pub(crate) fn leave(&mut self, previous: usize) -> Scope<D> {
+        Scope(self.scope.split_off(previous))
+    }
+
+    pub(crate) fn declare_self(&mut self, kind: &Kind) -> Result<(), KindError<D>> {
+        let self_ = self.self_.clone();
+        self.unify_or_err(&Type::self_(D::Meta::default(), vec![]), self_, kind.into())
+    }
+
+    fn generic(&mut self, gid: &D::Generic) -> IKind {
+        match self.scope.iter().rev().find(|(g, _)| g == gid) {
+            Some((_, kind)) => kind.clone(),
+            None => {
+                let kind = self.fresh();
+                self.scope.push((gid.clone(), kind.clone()));
+                kind
+            }
+        }
+    }
+
+    pub(crate) fn constraints(&mut self, generics: &Generics<D>) -> Result<(), KindError<D>> {
+        for (gid, constrs) in generics.iter() {
+            let of = Type::generic(D::Meta::default(), gid.clone(), vec![]);
+            let kind = self.generic(gid);
+            for con in constrs {
+                self.constraint(&of, kind.clone(), con)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn constraint(
+        &mut self,
+        of: &Type<D>,
+        kind: IKind,
+        con: &Constraint<D>,
+    ) -> Result<(), KindError<D>> {
+        if let Some(exp) = self.lookup.trait_(&con.trid) {
+            self.unify_or_err(of, kind, (&exp).into())?;
+        }
+        for param in &con.params {
+            let kind = self.fresh();
+            self.type_(param, kind)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn fully_applied(&mut self, t: &Type<D>) -> Result<(), KindError<D>> {
+        self.type_(t, IKind::Type)
+    }
+
+    pub(crate) fn of_kind(&mut self, t: &Type<D>, kind: &Kind) -> Result<(), KindError<D>> {
+        self.type_(t, kind.into())
+    }
+
+    fn type_(&mut self, t: &Type<D>, expected: IKind) -> Result<(), KindError<D>> {
+        if let TypeKind::Object(_) = &t.constr {
+            // the parameters of a trait object are the parameters of the trait
+            self.unify_or_err(t, IKind::Type, expected)?;
+            for param in &t.params {
+                let kind = self.fresh();
+                self.type_(param, kind)?;
+            }
+            return Ok(());
+        }
+
+        if let TypeKind::Forall(bound) = &t.constr {
+            let previous = self.scope.len();
+            for gid in bound {
+                let kind = self.fresh();
+                self.scope.push((gid.clone(), kind));
+            }
+            let result = t
+                .params
+                .iter()
+                .try_for_each(|body| self.type_(body, expected.clone()));
+            self.scope.truncate(previous);
+            return result;
+        }
+
+        let params = t.params.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+        let applied = params.iter().rev().fold(expected, |ret, p| {
+            IKind::Arrow(Box::new(p.clone()), Box::new(ret))
+        });
+
+        let constr = match &t.constr {
+            TypeKind::Generic(gid) => self.generic(gid),
+            TypeKind::Concrete(c) => match self.lookup.concrete(c) {
+                Some(kind) => (&kind).into(),
+                None => self.fresh(),
+            },
+            TypeKind::Self_ => self.self_.clone(),
+            TypeKind::Ref(_)
+            | TypeKind::Object(_)
+            | TypeKind::Forall(_)
+            | TypeKind::Opaque(_)
+            | TypeKind::Skolem(_)
+            | TypeKind::Hole => self.fresh(),
+        };
+
+        self.unify_or_err(t, constr, applied)?;
+
+        t.params
+            .iter()
+            .zip(params)
+            .try_for_each(|(param, kind)| self.type_(param, kind))
+    }
+
+    fn unify_or_err(&mut self, t: &Type<D>, got: IKind, exp: IKind) -> Result<(), KindError<D>> {
+        self.unify(&got, &exp).map_err(|err| match err {
+            UnifyError::Infinite => KindError::Infinite(t.clone()),
+            UnifyError::Missmatch => KindError::Missmatch {
+                type_: t.clone(),
+                expected: self.resolve(&exp),
+                got: self.resolve(&got),
+            },
+        })
+    }
+
+    fn prune(&self, kind: &IKind) -> IKind {
+        match kind {
+            IKind::Var(var) => match &self.vars[*var] {
+                Some(assigned) => self.prune(assigned),
+                None => kind.clone(),
+            },
+            _ => kind.clone(),
+        }
+    }
+
+    fn occurs(&self, var: usize, kind: &IKind) -> bool {
+        match self.prune(kind) {
+            IKind::Var(v) => v == var,
+            IKind::Type => false,
+            IKind::Arrow(param, ret) => self.occurs(var, &param) || self.occurs(var, &ret),
+        }
+    }
+
+    fn unify(&mut self, left: &IKind, right: &IKind) -> Result<(), UnifyError> {
+        match (self.prune(left), self.prune(right)) {
+            (IKind::Var(l), IKind::Var(r)) if l == r => Ok(()),
+            (IKind::Var(var), kind) | (kind, IKind::Var(var)) => {
+                if self.occurs(var, &kind) {
+                    Err(UnifyError::Infinite)
+                } else {
+                    self.vars[var] = Some(kind);
+                    Ok(())
+                }
+            }
+            (IKind::Type, IKind::Type) => Ok(()),
+            (IKind::Arrow(lparam, lret), IKind::Arrow(rparam, rret)) => {
+                self.unify(&lparam, &rparam)?;
+                self.unify(&lret, &rret)
+            }
+            _ => Err(UnifyError::Missmatch),
+        }
+    }
+
+    /// Any kinds which couldn't be infered default to `*`
+    fn resolve(&self, kind: &IKind) -> Kind {
+        match self.prune(kind) {
+            IKind::Var(_) | IKind::Type => Kind::Type,
+            IKind::Arrow(param, ret) => {
+                Kind::Arrow(Box::new(self.resolve(&param)), Box::new(self.resolve(&ret)))
+            }
+        }
+    }
+
+    pub(crate) fn kinds(&mut self, generics: &Generics<D>) -> Vec<(D::Generic, Kind)> {
+        generics
+            .iter()
+            .map(|(gid, _)| {
+                let kind = self.generic(gid);
+                (gid.clone(), self.resolve(&kind))
+            })
+            .collect()
+    }
+
+    pub(crate) fn scope_kinds(&self, scope: Scope<D>) -> Vec<(D::Generic, Kind)> {
+        scope
+            .0
+            .into_iter()
+            .map(|(gid, kind)| (gid, self.resolve(&kind)))
+            .collect()
+    }
+
+    pub(crate) fn self_kind(&self) -> Kind {
+        self.resolve(&self.self_)
+    }
+}
+
+impl From<&Kind> for IKind {
+    fn from(kind: &Kind) -> IKind {
+        match kind {
+            Kind::Type => IKind::Type,
+            Kind::Arrow(param, ret) => IKind::Arrow(
+                Box::new(param.as_ref().into()),
+                Box::new(ret.as_ref().into()),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Type => "*".fmt(f),
+            Kind::Arrow(param, ret) if matches!(**param, Kind::Arrow(..)) => {
+                write!(f, "({}) -> {}", param, ret)
+            }
+            Kind::Arrow(param, ret) => write!(f, "{} -> {}", param, ret),
+        }
+    }
+}
+
+impl<D: TypeData> fmt::Display for KindError<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KindError::Missmatch {
+                type_,
+                expected,
+                got,
+            } => {
+                write!(
+                    f,
+                    "`{}` is of kind `{}` but `{}` was expected",
+                    type_, got, expected
+                )
+            }
+            KindError::Infinite(type_) => write!(f, "`{}` would have an infinite kind", type_),
+        }
+    }
+}
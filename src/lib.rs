@@ -7,26 +7,62 @@ pub use r#type::{Key, Type, TypeData, TypeKind};
 pub type TypesBuf<D> = Vec<Type<D>>;
 pub type Types<D> = [Type<D>];
 
+mod kind;
+pub use kind::{Kind, KindError, KindLookup, Kinds};
+
+mod decls;
+pub use decls::{TypeDecl, TypeDecls};
+
 mod infer;
-pub use infer::RefID;
-pub use infer::TEnv;
+pub use infer::{RefID, Scope, TEnv};
 
 mod check;
 pub use check::{Error, ErrorHandler, TypeContext};
 
 mod query;
-pub use query::{Impl, TraitIndex};
+pub use query::{
+    Dispatch, DispatchError, Impl, MethodLookupError, MethodTable, ResolvedMethod, TraitIndex,
+};
 
 mod mapping;
-pub use mapping::Mapping;
+pub use mapping::{AnnotationError, ImplHeaderFailure, Mapping};
 
 pub mod frontend;
 
+mod pattern;
+pub use pattern::{MatchReport, Pattern, PatternError, Witness};
+
+mod variance;
+pub use variance::{Variance, Variances};
+
+mod wf;
+pub use wf::WfError;
+
 mod visitor;
 pub use visitor::TypeVisitor;
 
 #[cfg(test)]
 mod tests;
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ImplID(usize);
+
+/// A rigid type standing in for a generic within the scope declaring it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SkolemID(usize);
+
+impl std::fmt::Display for SkolemID {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "rigid#{}", self.0)
+    }
+}
+
+/// An opaque type whose concrete type is hidden from everything but its defining function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpaqueID(usize);
+
+impl std::fmt::Display for OpaqueID {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "impl#{}", self.0)
+    }
+}
@@ -1,6 +1,7 @@
+use crate::kind::KindInference;
 use crate::{
-    check, query, Constraint, ErrorHandler, Generics, RefID, TEnv, TraitIndex, Type, TypeContext,
-    TypeData, TypeKind, Types, TypesBuf,
+    check, query, Constraint, ErrorHandler, Generics, Kind, KindError, KindLookup, RefID, TEnv,
+    TraitIndex, Type, TypeContext, TypeData, TypeDecls, TypeKind, Types, TypesBuf, WfError,
 };
 use itertools::Itertools;
 use owo_colors::OwoColorize;
@@ -9,6 +10,7 @@ use std::fmt;
 #[derive(Debug, Clone)]
 pub struct Mapping<D: TypeData> {
     conversion: Vec<(D::Generic, RefID)>,
+    kinds: Vec<(D::Generic, Kind)>,
     self_: Option<RefID>,
 }
 
@@ -16,6 +18,7 @@ impl<D: TypeData> Default for Mapping<D> {
     fn default() -> Self {
         Self {
             conversion: vec![],
+            kinds: vec![],
             self_: None,
         }
     }
@@ -50,6 +53,20 @@ impl<D: TypeData> Mapping<D> {
         self.conversion.push((gid, rid))
     }
 
+    /// The kind of a generic, if it was annotated or infered
+    pub fn kind(&self, gid: &D::Generic) -> Option<&Kind> {
+        self.kinds
+            .iter()
+            .find_map(|(g, kind)| if g == gid { Some(kind) } else { None })
+    }
+
+    pub fn set_kind(&mut self, gid: D::Generic, kind: Kind) {
+        match self.kinds.iter_mut().find(|(g, _)| *g == gid) {
+            Some((_, k)) => *k = kind,
+            None => self.kinds.push((gid, kind)),
+        }
+    }
+
     pub fn assign_self(&mut self, rid: RefID) {
         self.self_ = Some(rid);
     }
@@ -69,101 +86,110 @@ impl<D: TypeData> Mapping<D> {
     }
 
     pub fn apply_type(&self, type_: &Type<D>) -> Type<D> {
-        type_.map_constr(&mut |_, constr| self.apply(constr))
+        type_.map_free_constr(&mut |constr| self.apply(constr))
     }
     pub fn apply_types(&self, types: &Types<D>) -> TypesBuf<D> {
         types.iter().map(|t| self.apply_type(t)).collect()
     }
 
+    /// Annotate `Self`, checking its kind against the declared kind of the trait
     pub fn annotate_self(
         &mut self,
         tenv: &mut TEnv<D>,
         index: &TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
+        decls: &TypeDecls<D>,
         type_: Type<D>,
     ) -> Result<(), AnnotationError<D>> {
         let rid = self.self_.expect("annotate_self called on non-trait");
-        Self::annotate_rid(tenv, index, rid, type_)
+        let kind = tenv
+            .constraints(rid)
+            .iter()
+            .find_map(|con| kinds.trait_(&con.trid));
+        Self::annotate_rid(tenv, index, kinds, decls, rid, kind.as_ref(), type_)
     }
 
     pub fn annotate_index(
         &mut self,
         tenv: &mut TEnv<D>,
         index: &TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
+        decls: &TypeDecls<D>,
         idx: usize,
         type_: Type<D>,
     ) -> Result<(), AnnotationError<D>> {
-        let (_, rid) = self.get_by_index(idx);
-        Self::annotate_rid(tenv, index, *rid, type_)
+        let (gid, rid) = self.get_by_index(idx);
+        let kind = self.kind(gid);
+        Self::annotate_rid(tenv, index, kinds, decls, *rid, kind, type_)
     }
 
+    /// Annotate a generic, checking the kind of the type against the kind of the generic.
+    ///
+    /// Higher-kinded generics are annotated with the bare type constructor, such as `option`
+    /// for a generic of kind `* -> *`.
     pub fn annotate_gid(
         &mut self,
         tenv: &mut TEnv<D>,
         index: &TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
+        decls: &TypeDecls<D>,
         gid: &D::Generic,
         type_: Type<D>,
     ) -> Result<(), AnnotationError<D>> {
         let rid = find(&self.conversion, gid).expect("Generic not defined");
-        Self::annotate_rid(tenv, index, rid, type_)
+        Self::annotate_rid(tenv, index, kinds, decls, rid, self.kind(gid), type_)
     }
 
     fn annotate_rid(
         tenv: &mut TEnv<D>,
         traits: &TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
+        decls: &TypeDecls<D>,
         rid: RefID,
+        kind: Option<&Kind>,
         annotated: Type<D>,
     ) -> Result<(), AnnotationError<D>> {
         let entry = tenv.get_mut(rid);
         if let Some(t) = &entry.assignment {
-            Err(AnnotationError::AlreadyAssigned(rid, t.value.clone()))
-        } else {
-            let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
-
-            // TODO: are higher-kinded types treated correctly if we just do this?
-            tctx.check_constraints_then_assign(rid, &[], annotated)
-                .map_err(|err| match err {
-                    check::Error::ConstraintNotMet(type_, constraints, contenders) => {
-                        AnnotationError::Constraint(type_, constraints, contenders)
-                    }
-                    _ => unreachable!(
-                        "since we hardcode 0 type params, no check errors should be able to occour"
-                    ),
-                })
+            return Err(AnnotationError::AlreadyAssigned(rid, t.value.clone()));
+        }
+
+        if let Some(kind) = kind {
+            KindInference::new(kinds)
+                .of_kind(&annotated, kind)
+                .map_err(AnnotationError::Kind)?;
         }
+
+        decls
+            .well_formed(tenv, traits, &annotated)
+            .map_err(AnnotationError::WellFormed)?;
+
+        // The params of higher-kinded usages are appended once resolved, so a bare type
+        // constructor is assigned as-is and constraints are checked against the constructor.
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive).with_kinds(kinds);
+        tctx.check_constraints_then_assign(rid, &[], annotated)
+            .map_err(AnnotationError::from)
     }
 
     pub fn annotate_types(
         &mut self,
         tenv: &mut TEnv<D>,
         traits: &TraitIndex<D>,
+        kinds: &dyn KindLookup<D>,
+        decls: &TypeDecls<D>,
         types: &Types<D>,
-    ) -> Result<(), check::Error<D>> {
-        Self::annotate_type_params_raw(&self.conversion, tenv, traits, types)
-    }
+    ) -> Result<(), AnnotationError<D>> {
+        let (got, exp) = (types.len(), self.conversion.len());
+        if got != exp {
+            return Err(AnnotationError::ParamCount { got, exp });
+        }
 
-    fn annotate_type_params_raw(
-        buf: &[(D::Generic, RefID)],
-        tenv: &mut TEnv<D>,
-        traits: &TraitIndex<D>,
-        types: &[Type<D>],
-    ) -> Result<(), check::Error<D>> {
-        assert_eq!(
-            buf.len(),
-            types.len(),
-            "length of applicated types for type doesn't match the types declaration"
-        );
-        buf.iter().zip(types).try_for_each(|((_, rid), t)| {
-            let tentry = tenv.get(*rid);
-            assert!(
-                tentry.assignment.is_none(),
-                "annotate_type_params called on already annotated mapping"
-            );
-            TypeContext::new(tenv, traits, ErrorHandler::Expensive).check_constraints_then_assign(
-                *rid,
-                &[],
-                t.clone(),
-            )
-        })
+        self.conversion
+            .iter()
+            .zip(types)
+            .try_for_each(|((gid, rid), t)| {
+                Self::annotate_rid(tenv, traits, kinds, decls, *rid, self.kind(gid), t.clone())
+            })
     }
 }
 
@@ -171,6 +197,25 @@ impl<D: TypeData> Mapping<D> {
 pub enum AnnotationError<D: TypeData> {
     AlreadyAssigned(RefID, Type<D>),
     Constraint(Type<D>, Constraint<D>, Vec<query::Contender>),
+    Kind(KindError<D>),
+    ParamCount {
+        got: usize,
+        exp: usize,
+    },
+    Check(check::Error<D>),
+    /// The annotated type doesn't satisfy the declarations of its type constructors
+    WellFormed(Vec<WfError<D>>),
+}
+
+impl<D: TypeData> From<check::Error<D>> for AnnotationError<D> {
+    fn from(err: check::Error<D>) -> Self {
+        match err {
+            check::Error::ConstraintNotMet(type_, constraint, contenders) => {
+                AnnotationError::Constraint(type_, constraint, contenders)
+            }
+            err => AnnotationError::Check(err),
+        }
+    }
 }
 
 /// Maps the trait declarations generics to the implementations generics
@@ -179,6 +224,9 @@ pub struct ImplToTrait<'t, D: TypeData> {
     trait_mapping: &'t Mapping<D>,
     tenv: &'t TEnv<D>,
     generics: Generics<D>,
+    expected: Option<&'t Generics<D>>,
+    /// What the implementation used in place of the trait's unassigned references
+    unassigned: Vec<(RefID, Type<D>)>,
 
     errbuf: Vec<ImplHeaderFailure<D>>,
 }
@@ -193,6 +241,8 @@ pub enum ImplHeaderFailure<D: TypeData> {
         got: Type<D>,
         exp: Type<D>,
     },
+    /// The implementation's signature contains a type which is still being infered
+    RefInSignature(RefID),
 }
 
 impl<'t, D: TypeData> ImplToTrait<'t, D> {
@@ -201,6 +251,8 @@ impl<'t, D: TypeData> ImplToTrait<'t, D> {
             conversion: vec![],
             generics,
             tenv,
+            expected: None,
+            unassigned: vec![],
 
             trait_mapping,
 
@@ -208,6 +260,12 @@ impl<'t, D: TypeData> ImplToTrait<'t, D> {
         }
     }
 
+    /// Use the generics of the trait's method when declaring generics for inference holes
+    pub fn expecting(mut self, generics: &'t Generics<D>) -> Self {
+        self.expected = Some(generics);
+        self
+    }
+
     fn missmatch(&mut self, got: Type<D>, exp: Type<D>) {
         self.errbuf.push(ImplHeaderFailure::Missmatch { got, exp })
     }
@@ -221,14 +279,34 @@ impl<'t, D: TypeData> ImplToTrait<'t, D> {
             .push(ImplHeaderFailure::ConflictingGeneric { in_method, from_impl_block });
     }
 
+    /// Check a type from the implementation against the type the trait expects
+    ///
+    /// The implementation's signature is a declaration and may not contain references, except
+    /// for inference holes created with [`Type::hole`].
     pub fn type_(&mut self, got: Type<D>, exp: &Type<D>) -> Type<D> {
         let constr = match (&got.constr, &exp.constr) {
-            (TypeKind::Self_, TypeKind::Self_) => TypeKind::Ref(self.trait_mapping.self_.unwrap()),
+            // Inference holes left by the implementation are filled by the trait's signature
+            (TypeKind::Hole, _) => return self.fill(got, exp),
+            (TypeKind::Ref(rid), _) => {
+                self.errbuf.push(ImplHeaderFailure::RefInSignature(*rid));
+                return got;
+            }
+            (_, TypeKind::Ref(rid)) => match self.tenv.get_type(*rid) {
+                Some(assigned) => return self.type_(got, assigned),
+                None => return self.unassigned(*rid, got),
+            },
             (_, TypeKind::Self_) => {
                 let rid = self.trait_mapping.self_.unwrap();
-                TypeKind::Ref(rid)
+                match self.tenv.get_type(rid) {
+                    Some(self_) if exp.params.is_empty() => {
+                        if got.constr == TypeKind::Self_ {
+                            return self_.clone();
+                        }
+                        return self.type_(got, &self_.clone());
+                    }
+                    _ => TypeKind::Ref(rid),
+                }
             }
-            (TypeKind::Ref(_), _) | (_, TypeKind::Ref(_)) => panic!("unexpected inference"),
             (TypeKind::Concrete(gcon), TypeKind::Concrete(econ)) if gcon == econ => got.constr,
 
             (gconstr, TypeKind::Generic(rgid)) => {
@@ -292,6 +370,118 @@ impl<'t, D: TypeData> ImplToTrait<'t, D> {
         }
     }
 
+    /// The trait leaves `rid` open, so the implementation may pick any type for it as long as it
+    /// picks the same type each time.
+    fn unassigned(&mut self, rid: RefID, got: Type<D>) -> Type<D> {
+        match self.unassigned.iter().find(|(r, _)| *r == rid) {
+            Some((_, first)) => {
+                if !got.direct_eq(first) {
+                    let first = first.clone();
+                    self.missmatch(got.clone(), first);
+                }
+            }
+            None => self.unassigned.push((rid, got.clone())),
+        }
+        got
+    }
+
+    fn fill(&mut self, hole: Type<D>, exp: &Type<D>) -> Type<D> {
+        if hole.params.len() > exp.params.len() {
+            self.missmatch(hole.clone(), exp.clone());
+            return hole;
+        }
+
+        // A hole applied to parameters only stands in for the constructor
+        let at = exp.params.len() - hole.params.len();
+        let mut filled = self.translate(&Type {
+            meta: hole.meta,
+            constr: exp.constr.clone(),
+            params: exp.params[..at].to_vec(),
+        });
+        let params = self.types(hole.params, &exp.params[at..]);
+        filled.params.extend(params);
+        filled
+    }
+
+    /// Convert a type from the trait's signature into the terms of the implementation
+    fn translate(&mut self, exp: &Type<D>) -> Type<D> {
+        exp.map_type(&mut |meta, constr, params| {
+            let applied = |mut t: Type<D>, params| {
+                t.params.extend(params);
+                t
+            };
+
+            match constr {
+                TypeKind::Self_ => {
+                    let rid = self.trait_mapping.self_.unwrap();
+                    match self.tenv.get_type(rid) {
+                        Some(self_) => applied(self.tenv.concretify_type(self_), params),
+                        None => Type::reference(meta, rid, params),
+                    }
+                }
+                TypeKind::Generic(rgid) => match self.trait_mapping.resolve_gid(rgid) {
+                    Some(rid) => {
+                        let assigned = Type::reference(meta, rid, vec![]);
+                        applied(self.tenv.concretify_type(&assigned), params)
+                    }
+                    None => Type::generic(meta, self.method_generic(rgid), params),
+                },
+                _ => Type {
+                    meta,
+                    constr: constr.clone(),
+                    params,
+                },
+            }
+        })
+    }
+
+    /// Get the implementation's name for a generic declared by the trait's method, declaring it
+    /// if the implementation hasn't mentioned it yet.
+    fn method_generic(&mut self, rgid: &D::Generic) -> D::Generic {
+        if let Some((lgid, _)) = self.conversion.iter().find(|(_, to)| to == rgid) {
+            return lgid.clone();
+        }
+
+        let mut taken = self.generics.clone();
+        let assigned = self
+            .trait_mapping
+            .conversion
+            .iter()
+            .map(|(_, rid)| *rid)
+            .chain(self.trait_mapping.self_);
+        for rid in assigned {
+            if let Some(t) = self.tenv.get_type(rid) {
+                self.tenv.concretify_type(t).map_constr(&mut |_, constr| {
+                    if let TypeKind::Generic(gid) = constr {
+                        if !taken.contains(gid.clone()) {
+                            taken.insert(gid.clone());
+                        }
+                    }
+                    constr.clone()
+                });
+            }
+        }
+
+        let lgid = D::first_available(&taken);
+        self.conversion.push((lgid.clone(), rgid.clone()));
+        self.generics.insert(lgid.clone());
+
+        if let Some(constrs) = self.expected.and_then(|g| g.try_constraints(rgid)) {
+            let constrs = constrs
+                .iter()
+                .map(|con| {
+                    Constraint::new(
+                        con.trid.clone(),
+                        con.params.iter().map(|t| self.translate(t)).collect(),
+                    )
+                })
+                .collect();
+            self.generics.update_with_cons(lgid.clone(), constrs);
+        }
+
+        lgid
+    }
+
     pub fn types(&mut self, got: TypesBuf<D>, exp: &Types<D>) -> TypesBuf<D> {
         got.into_iter()
             .zip(exp.iter())
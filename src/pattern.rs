@@ -0,0 +1,353 @@
+use crate::decls::substitute;
+use crate::frontend::UnknownField;
+use crate::{Generics, Type, TypeData, TypeDecl, TypeDecls, TypeKind};
+use itertools::Itertools;
+use std::fmt;
+
+/// A pattern of a match arm over values of declared types
+#[derive(Debug, Clone)]
+pub enum Pattern<D: TypeData> {
+    Wildcard,
+    /// A variant of a sum type by its index, with patterns for the variant's parameters
+    Variant(usize, Vec<Pattern<D>>),
+    /// Destructuring of a product type, fields which aren't mentioned are matched by wildcards
+    Product(Vec<(D::Field, Pattern<D>)>),
+}
+
+/// A value not matched by any arm, with wildcards standing in for any value
+#[derive(Debug, Clone)]
+pub enum Witness<D: TypeData> {
+    Wildcard,
+    Variant {
+        sum: D::Concrete,
+        variant: usize,
+        params: Vec<Witness<D>>,
+    },
+    Product {
+        product: D::Concrete,
+        fields: Vec<(D::Field, Witness<D>)>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchReport<D: TypeData> {
+    /// Values not covered by any arm, empty if the match is exhaustive
+    pub missing: Vec<Witness<D>>,
+    /// Indices of the arms which can never match since previous arms cover all their values
+    pub unreachable: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatternError<D: TypeData> {
+    /// The pattern can not match values of this type
+    Missmatch(Type<D>),
+    UnknownField(UnknownField<D>),
+}
+
+/// Patterns lowered to constructors by their index, with product types having a single one
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(usize, Vec<Pat>),
+}
+
+type Row = Vec<Pat>;
+
+impl<D: TypeData> TypeDecls<D> {
+    /// Check a match over the scrutinee type for missing and unreachable arms.
+    ///
+    /// Types without a declaration can only be matched by wildcards, and are considered to have
+    /// infinitely many values. Fails with the index of every arm which doesn't fit the type.
+    pub fn check_match(
+        &self,
+        scrutinee: &Type<D>,
+        arms: &[Pattern<D>],
+    ) -> Result<MatchReport<D>, Vec<(usize, PatternError<D>)>> {
+        let mut rows = Vec::with_capacity(arms.len());
+        let mut errors = vec![];
+
+        for (i, arm) in arms.iter().enumerate() {
+            match self.lower(arm, scrutinee) {
+                Ok(pat) => rows.push(vec![pat]),
+                Err(err) => errors.push((i, err)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let types = vec![scrutinee.clone()];
+
+        let unreachable = (0..rows.len())
+            .filter(|&i| !self.useful(&rows[..i], &rows[i], &types))
+            .collect();
+
+        let missing = self
+            .missing(&rows, &types)
+            .into_iter()
+            .map(|mut witness| witness.remove(0))
+            .collect();
+
+        Ok(MatchReport {
+            missing,
+            unreachable,
+        })
+    }
+
+    fn lower(&self, pattern: &Pattern<D>, t: &Type<D>) -> Result<Pat, PatternError<D>> {
+        let missmatch = || PatternError::Missmatch(t.clone());
+
+        match pattern {
+            Pattern::Wildcard => Ok(Pat::Wild),
+            Pattern::Variant(variant, params) => match self.decl_of(t) {
+                Some(TypeDecl::Sum(_)) => {
+                    let ptypes = self
+                        .constructors(t)
+                        .and_then(|mut ctors| {
+                            if *variant < ctors.len() {
+                                Some(ctors.swap_remove(*variant))
+                            } else {
+                                None
+                            }
+                        })
+                        .filter(|ptypes| ptypes.len() == params.len())
+                        .ok_or_else(missmatch)?;
+
+                    let params = params
+                        .iter()
+                        .zip(&ptypes)
+                        .map(|(p, t)| self.lower(p, t))
+                        .collect::<Result<_, _>>()?;
+
+                    Ok(Pat::Ctor(*variant, params))
+                }
+                _ => Err(missmatch()),
+            },
+            Pattern::Product(fields) => match self.decl_of(t) {
+                Some(TypeDecl::Product(product)) => {
+                    let ftypes = self.constructors(t).unwrap().remove(0);
+                    let mut params = vec![Pat::Wild; ftypes.len()];
+
+                    for (name, p) in fields {
+                        let i = product
+                            .product
+                            .fields()
+                            .position(|field| field == name)
+                            .ok_or_else(|| {
+                                PatternError::UnknownField(UnknownField {
+                                    product: product.product.identifier.clone(),
+                                    field: name.clone(),
+                                })
+                            })?;
+
+                        params[i] = self.lower(p, &ftypes[i])?;
+                    }
+
+                    Ok(Pat::Ctor(0, params))
+                }
+                _ => Err(missmatch()),
+            },
+        }
+    }
+
+    fn decl_of(&self, t: &Type<D>) -> Option<&TypeDecl<D>> {
+        match &t.constr {
+            TypeKind::Concrete(constr) => self
+                .get(constr)
+                .filter(|decl| decl.arity() == t.params.len()),
+            _ => None,
+        }
+    }
+
+    /// The parameter types of each constructor of the type, or `None` if it isn't a fully applied
+    /// declared type
+    fn constructors(&self, t: &Type<D>) -> Option<Vec<Vec<Type<D>>>> {
+        let decl = self.decl_of(t)?;
+        let params = &t.params;
+        let generics = decl.generics();
+
+        Some(match decl {
+            TypeDecl::Product(product) => vec![product
+                .product
+                .field_types()
+                .map(|t| substitute(generics, &Generics::new(), params, t))
+                .collect::<Option<_>>()?],
+            TypeDecl::Sum(sum) => sum
+                .sum
+                .variants()
+                .iter()
+                .zip(sum.sum.existentials())
+                .map(|(ptypes, existentials)| {
+                    ptypes
+                        .iter()
+                        .map(|t| substitute(generics, existentials, params, t))
+                        .collect()
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Whether there's a value matched by `q` which isn't matched by any of the rows
+    fn useful(&self, rows: &[Row], q: &[Pat], types: &[Type<D>]) -> bool {
+        let (head, rest) = match q.split_first() {
+            None => return rows.is_empty(),
+            Some(split) => split,
+        };
+
+        let ctors = self.constructors(&types[0]);
+
+        match head {
+            Pat::Ctor(c, params) => {
+                let ctors = ctors.unwrap();
+                let q = params.iter().chain(rest).cloned().collect::<Row>();
+                let types = sub_types(&ctors[*c], types);
+                self.useful(&specialize(rows, *c, ctors[*c].len()), &q, &types)
+            }
+            Pat::Wild => match ctors.filter(|ctors| is_complete(rows, ctors.len())) {
+                Some(ctors) => ctors.iter().enumerate().any(|(c, ptypes)| {
+                    let q = vec![Pat::Wild; ptypes.len()]
+                        .into_iter()
+                        .chain(rest.iter().cloned())
+                        .collect::<Row>();
+                    let types = sub_types(ptypes, types);
+                    self.useful(&specialize(rows, c, ptypes.len()), &q, &types)
+                }),
+                None => self.useful(&default(rows), rest, &types[1..]),
+            },
+        }
+    }
+
+    /// Every combination of values for the columns which isn't matched by any of the rows
+    fn missing(&self, rows: &[Row], types: &[Type<D>]) -> Vec<Vec<Witness<D>>> {
+        let t = match types.first() {
+            None if rows.is_empty() => return vec![vec![]],
+            None => return vec![],
+            Some(t) => t,
+        };
+
+        let ctors = self.constructors(t);
+
+        match ctors.filter(|ctors| is_complete(rows, ctors.len())) {
+            // Every constructor is matched at the head, so the values are missing deeper inside
+            Some(ctors) => ctors
+                .iter()
+                .enumerate()
+                .flat_map(|(c, ptypes)| {
+                    let types = sub_types(ptypes, types);
+                    self.missing(&specialize(rows, c, ptypes.len()), &types)
+                        .into_iter()
+                        .map(move |mut witness| {
+                            let rest = witness.split_off(ptypes.len());
+                            let mut row = vec![self.witness(t, c, witness)];
+                            row.extend(rest);
+                            row
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+
+            None => {
+                let rest = self.missing(&default(rows), &types[1..]);
+                let heads = match self.constructors(t) {
+                    Some(ctors) if rows.iter().any(|row| matches!(row[0], Pat::Ctor(..))) => ctors
+                        .iter()
+                        .enumerate()
+                        .filter(|(c, _)| !rows.iter().any(|row| is_ctor(&row[0], *c)))
+                        .map(|(c, ptypes)| {
+                            let params = vec![Witness::Wildcard; ptypes.len()];
+                            self.witness(t, c, params)
+                        })
+                        .collect(),
+                    _ => vec![Witness::Wildcard],
+                };
+
+                rest.into_iter()
+                    .flat_map(|witness| {
+                        heads.iter().map(move |head| {
+                            let mut row = vec![head.clone()];
+                            row.extend(witness.iter().cloned());
+                            row
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn witness(&self, t: &Type<D>, c: usize, params: Vec<Witness<D>>) -> Witness<D> {
+        match self.decl_of(t) {
+            Some(TypeDecl::Sum(sum)) => Witness::Variant {
+                sum: sum.sum.identifier.clone(),
+                variant: c,
+                params,
+            },
+            Some(TypeDecl::Product(product)) => Witness::Product {
+                product: product.product.identifier.clone(),
+                fields: product.product.fields().cloned().zip(params).collect(),
+            },
+            None => Witness::Wildcard,
+        }
+    }
+}
+
+fn sub_types<D: TypeData>(ptypes: &[Type<D>], types: &[Type<D>]) -> Vec<Type<D>> {
+    ptypes.iter().chain(&types[1..]).cloned().collect()
+}
+
+fn is_ctor(pat: &Pat, c: usize) -> bool {
+    matches!(pat, Pat::Ctor(pc, _) if *pc == c)
+}
+
+/// Whether every constructor of the type appears at the head of the rows
+fn is_complete(rows: &[Row], ctors: usize) -> bool {
+    (0..ctors).all(|c| rows.iter().any(|row| is_ctor(&row[0], c)))
+}
+
+/// The rows which match constructor `c`, with their head replaced by the constructor's params
+fn specialize(rows: &[Row], c: usize, arity: usize) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let head = match &row[0] {
+                Pat::Ctor(pc, params) if *pc == c => params.clone(),
+                Pat::Ctor(..) => return None,
+                Pat::Wild => vec![Pat::Wild; arity],
+            };
+            Some(head.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// The rows which match any value at their head, with it removed
+fn default(rows: &[Row]) -> Vec<Row> {
+    rows.iter()
+        .filter(|row| matches!(row[0], Pat::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+impl<D: TypeData> fmt::Display for Witness<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Witness::Wildcard => "_".fmt(f),
+            Witness::Variant {
+                sum,
+                variant,
+                params,
+            } if params.is_empty() => write!(f, "{}:{}", sum, variant),
+            Witness::Variant {
+                sum,
+                variant,
+                params,
+            } => write!(f, "({}:{} {})", sum, variant, params.iter().format(" ")),
+            Witness::Product { product, fields } => write!(
+                f,
+                "{{ {} . {} }}",
+                product,
+                fields
+                    .iter()
+                    .format_with(", ", |(name, w), f| f(&format_args!("{}: {}", name, w)))
+            ),
+        }
+    }
+}
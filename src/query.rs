@@ -1,6 +1,7 @@
+use crate::frontend::{ForeignFunction, ForeignTrait, Function};
 use crate::{
-    ErrorHandler, Generics, ImplID, Mapping, TEnv, Type, TypeContext, TypeData, TypeKind, Types,
-    TypesBuf,
+    Constraint, ErrorHandler, Generics, ImplID, KindLookup, Mapping, OpaqueID, TEnv, Type,
+    TypeContext, TypeData, TypeKind, Types, TypesBuf,
 };
 use itertools::Itertools;
 use smallvec::SmallVec;
@@ -9,6 +10,11 @@ use std::fmt;
 
 pub type AssociatedTypes<D> = Vec<(<D as TypeData>::Generic, Type<D>)>;
 
+/// The functions implementing each of the trait's methods, indexed by the trait's method index
+///
+/// Methods left as `None` fall back to the trait's default implementation.
+pub type MethodTable<D> = Vec<Option<<D as TypeData>::Function>>;
+
 #[derive(Debug)]
 pub struct Impl<D: TypeData> {
     pub forall: Generics<D>,
@@ -16,6 +22,10 @@ pub struct Impl<D: TypeData> {
     pub impltor: Type<D>,
 
     pub implid: ImplID,
+    pub methods: MethodTable<D>,
+    /// The signatures of the implementation's own methods with any inference holes filled in,
+    /// only known for implementations validated by [`ForeignTrait::implement`]
+    pub signatures: Vec<Option<ForeignFunction<D>>>,
     pub associated: AssociatedTypes<D>,
 }
 
@@ -23,12 +33,14 @@ pub struct Impl<D: TypeData> {
 pub struct TraitIndex<D: TypeData> {
     trids: HashMap<D::Trait, Variants<D>>,
     count: usize,
+    opaques: usize,
 }
 
 #[derive(Debug)]
 struct Variants<D: TypeData> {
     concrete: HashMap<D::Concrete, Vec<Impl<D>>>,
     object: HashMap<D::Trait, Vec<Impl<D>>>,
+    opaque: HashMap<OpaqueID, Vec<Impl<D>>>,
     blanked: Vec<Impl<D>>,
 
     default: Option<Impl<D>>,
@@ -46,40 +58,113 @@ impl<D: TypeData> TraitIndex<D> {
         TraitIndex {
             trids: HashMap::new(),
             count: 0,
+            opaques: 0,
         }
     }
 
+    /// Declare an opaque type over `generics` which implements each of the constraints.
+    ///
+    /// The opaque type is used applied to the generics, and the constraints may refer to them.
+    pub fn implement_opaque(
+        &mut self,
+        generics: Generics<D>,
+        constraints: Vec<Constraint<D>>,
+    ) -> OpaqueID {
+        let id = OpaqueID(self.opaques);
+        self.opaques += 1;
+
+        let impltor = Type {
+            constr: TypeKind::Opaque(id),
+            meta: D::Meta::default(),
+            params: generics
+                .iter()
+                .map(|(gid, _)| Type::generic(D::Meta::default(), gid.clone(), vec![]))
+                .collect(),
+        };
+
+        for con in constraints {
+            self.implement(
+                generics.clone(),
+                con.trid,
+                con.params,
+                impltor.clone(),
+                vec![],
+                vec![],
+            );
+        }
+
+        id
+    }
+
     pub fn implement(
         &mut self,
         generics: Generics<D>,
         trid: D::Trait,
         trtp: TypesBuf<D>,
         impltor: Type<D>,
+        methods: MethodTable<D>,
         associated: AssociatedTypes<D>,
-    ) {
+    ) -> ImplID {
+        let implid = self.next_implid();
+        self.insert(
+            trid,
+            Impl {
+                forall: generics,
+                trait_type_params: trtp,
+                impltor,
+                methods,
+                signatures: vec![],
+                associated,
+                implid,
+            },
+        )
+    }
+
+    /// Register an implementation along with the validated signatures of its methods
+    pub(crate) fn implement_with_signatures(
+        &mut self,
+        generics: Generics<D>,
+        trid: D::Trait,
+        trtp: TypesBuf<D>,
+        impltor: Type<D>,
+        methods: MethodTable<D>,
+        signatures: Vec<Option<ForeignFunction<D>>>,
+    ) -> ImplID {
+        let implid = self.next_implid();
+        self.insert(
+            trid,
+            Impl {
+                forall: generics,
+                trait_type_params: trtp,
+                impltor,
+                methods,
+                signatures,
+                associated: vec![],
+                implid,
+            },
+        )
+    }
+
+    fn next_implid(&mut self) -> ImplID {
+        let implid = ImplID(self.count);
+        self.count += 1;
+        implid
+    }
+
+    fn insert(&mut self, trid: D::Trait, impl_: Impl<D>) -> ImplID {
         let tvariant = self.trids.entry(trid).or_insert_with(Variants::new);
 
-        let constr = impltor.constr.clone();
+        let constr = impl_.impltor.constr.clone();
+        let implid = impl_.implid;
 
         #[cfg(debug_assertions)]
-        impltor.map_constr(&mut |_, con| match con {
-            TypeKind::Ref(_) | TypeKind::Self_ => {
+        impl_.impltor.map_constr(&mut |_, con| match con {
+            TypeKind::Ref(_) | TypeKind::Self_ | TypeKind::Forall(_) => {
                 panic!("invalid constructor for implementor of trait: {:?}", con)
             }
             other => other.clone(),
         });
 
-        let implid = ImplID(self.count);
-        self.count += 1;
-
-        let impl_ = Impl {
-            forall: generics,
-            trait_type_params: trtp,
-            impltor,
-            associated,
-            implid,
-        };
-
         match constr {
             TypeKind::Generic(_) => tvariant.blanked.push(impl_),
             TypeKind::Concrete(c) => tvariant
@@ -92,17 +177,25 @@ impl<D: TypeData> TraitIndex<D> {
                 .entry(trid)
                 .or_insert_with(Vec::new)
                 .push(impl_),
+            TypeKind::Opaque(id) => tvariant.opaque.entry(id).or_default().push(impl_),
             _ => unreachable!(),
         }
+
+        implid
     }
 
-    pub fn select(
-        &self,
+    /// Select the implementations of `trait_` for `impltor`
+    ///
+    /// Implementations of traits declared higher-kinded in `kinds`, such as `Functor`, are also
+    /// selected for applications of their implementor.
+    pub fn select<'s>(
+        &'s self,
         tenv: &TEnv<D>,
+        kinds: &dyn KindLookup<D>,
         trait_: D::Trait,
         trait_params: &Types<D>,
         impltor: &Type<D>,
-    ) -> Result<SmallVec<[QuerySuccess<'_, D>; 1]>, Vec<Contender>> {
+    ) -> Result<SmallVec<[QuerySuccess<'s, D>; 1]>, Vec<Contender>> {
         let variants = match self.trids.get(&trait_) {
             None => return Err(vec![]),
             Some(variants) => variants,
@@ -110,18 +203,197 @@ impl<D: TypeData> TraitIndex<D> {
 
         Selection {
             tenv,
-            // trait_,
+            kinds,
+            trait_: &trait_,
             trait_params,
             traits: self,
         }
         .run(impltor, variants)
     }
+
+    /// Statically dispatch a call to the method `mid` of `trait_` for a fully infered `self_`
+    ///
+    /// Returns the function implementing the method along with the mapping of the selected
+    /// implementation's generics, which is what's needed to monomorphise the call.
+    pub fn dispatch(
+        &self,
+        tenv: &TEnv<D>,
+        kinds: &dyn KindLookup<D>,
+        trait_: &ForeignTrait<D>,
+        trait_params: &Types<D>,
+        self_: &Type<D>,
+        mid: usize,
+    ) -> Result<Dispatch<'_, D>, DispatchError> {
+        assert!(
+            mid < trait_.methods().len(),
+            "method index out of bounds for trait"
+        );
+
+        if !tenv.is_resolved(self_) {
+            return Err(DispatchError::Uninfered);
+        }
+
+        let mut matches = self
+            .select(tenv, kinds, trait_.identifier.clone(), trait_params, self_)
+            .map_err(DispatchError::NotImplemented)?;
+
+        if matches.len() != 1 {
+            return Err(DispatchError::Ambiguous);
+        }
+
+        let QuerySuccess {
+            impl_,
+            mapping,
+            tenv,
+            ..
+        } = matches.remove(0);
+
+        let (function, defaulted) = match impl_.methods.get(mid).cloned().flatten() {
+            Some(function) => (function, false),
+            None => match trait_.methods()[mid].default.clone() {
+                Some(function) => (function, true),
+                None => return Err(DispatchError::MissingMethod),
+            },
+        };
+
+        Ok(Dispatch {
+            function,
+            defaulted,
+            impl_,
+            mapping,
+            tenv,
+        })
+    }
+
+    /// Resolve a method call by name such as `x.show()`
+    ///
+    /// Searches the traits in scope for the ones declaring `name` and implemented by `receiver`.
+    /// The method of the single matching trait is then instantiated into `tenv`.
+    pub fn resolve_method(
+        &self,
+        tenv: &mut TEnv<D>,
+        kinds: &dyn KindLookup<D>,
+        in_scope: &[&ForeignTrait<D>],
+        name: &D::Method,
+        receiver: &Type<D>,
+    ) -> Result<ResolvedMethod<D>, MethodLookupError<D>> {
+        if let TypeKind::Ref(_) = tenv.concretify_type(receiver).constr {
+            return Err(MethodLookupError::UnresolvedReceiver);
+        }
+
+        let mut declaring = vec![];
+        let mut candidates = vec![];
+
+        for trait_ in in_scope {
+            let mid = match trait_.method_position(name) {
+                Some(mid) => mid,
+                None => continue,
+            };
+
+            declaring.push(trait_.identifier.clone());
+
+            let mut scratch = tenv.clone();
+            let inst = trait_.instantiate(&mut scratch);
+            let trait_params = inst.trait_params(receiver.meta.clone());
+
+            if let Ok(matches) = self.select(
+                &scratch,
+                kinds,
+                trait_.identifier.clone(),
+                &trait_params,
+                receiver,
+            ) {
+                candidates.push((mid, inst, trait_params, matches));
+            }
+        }
+
+        match candidates.len() {
+            0 if declaring.is_empty() => Err(MethodLookupError::NotFound),
+            0 => Err(MethodLookupError::NotImplemented(declaring)),
+            1 if candidates[0].3.len() == 1 => {
+                let (mid, inst, trait_params, mut matches) = candidates.remove(0);
+                let QuerySuccess { tenv: selected, .. } = matches.remove(0);
+
+                *tenv = selected;
+                inst.set_self(receiver.clone(), tenv);
+
+                Ok(ResolvedMethod {
+                    trid: inst.trid.clone(),
+                    mid,
+                    trait_params: tenv.concretify_types(&trait_params),
+                    function: inst.method(mid, tenv),
+                })
+            }
+            1 => Err(MethodLookupError::OverlappingImpls(
+                candidates[0].1.trid.clone(),
+            )),
+            _ => Err(MethodLookupError::Ambiguous(
+                candidates
+                    .iter()
+                    .map(|(_, inst, _, _)| inst.trid.clone())
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// The implementation selected for a statically dispatched trait method
+#[derive(Debug)]
+pub struct Dispatch<'a, D: TypeData> {
+    pub function: D::Function,
+    /// Whether `function` is the trait's default rather than the implementation's own method
+    pub defaulted: bool,
+    pub impl_: &'a Impl<D>,
+    pub mapping: Mapping<D>,
+    pub tenv: TEnv<D>,
+}
+
+impl<'a, D: TypeData> Dispatch<'a, D> {
+    /// The types the implementation's generics were instantiated to, in order of declaration
+    pub fn impl_params(&self, meta: D::Meta) -> TypesBuf<D> {
+        self.tenv.concretify_types(&self.mapping.to_types(meta))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DispatchError {
+    NotImplemented(Vec<Contender>),
+    /// More than one implementation matched
+    Ambiguous,
+    /// The selected implementation doesn't provide this method and the trait has no default
+    MissingMethod,
+    /// `self` still contains types pending inference
+    Uninfered,
+}
+
+/// A trait method resolved by name for a receiver type
+#[derive(Debug, Clone)]
+pub struct ResolvedMethod<D: TypeData> {
+    pub trid: D::Trait,
+    pub mid: usize,
+    pub trait_params: TypesBuf<D>,
+    pub function: Function<D>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MethodLookupError<D: TypeData> {
+    /// None of the traits in scope declare a method by this name
+    NotFound,
+    /// The receiver doesn't implement any of the traits declaring the method
+    NotImplemented(Vec<D::Trait>),
+    /// More than one of the traits declaring the method is implemented by the receiver
+    Ambiguous(Vec<D::Trait>),
+    /// More than one implementation of the trait matches the receiver
+    OverlappingImpls(D::Trait),
+    /// The receiver is not infered enough to select an implementation
+    UnresolvedReceiver,
 }
 
 struct Selection<'a, D: TypeData> {
     tenv: &'a TEnv<D>,
+    kinds: &'a dyn KindLookup<D>,
     traits: &'a TraitIndex<D>,
-    // trait_: D::Trait,
+    trait_: &'a D::Trait,
     trait_params: &'a Types<D>,
 }
 
@@ -135,13 +407,14 @@ impl<'a, D: TypeData> Selection<'a, D> {
         let mut contenders = Vec::new();
 
         match &impltor.constr {
-            TypeKind::Generic(_) => self.filter_suitible(&variants.blanked, impltor, &mut results, &mut contenders),
+            TypeKind::Generic(_) | TypeKind::Forall(_) | TypeKind::Skolem(_) => self.filter_suitible(&variants.blanked, impltor, &mut results, &mut contenders),
             TypeKind::Concrete(c) => {
                 if let Some(impls) = variants.concrete.get(c) {
                     self.filter_suitible(impls, impltor, &mut results, &mut contenders);
                 }
                 self.filter_suitible(&variants.blanked, impltor, &mut results, &mut contenders);
             },
+            TypeKind::Hole => unreachable!("inference holes are replaced with references on instantiation"),
             TypeKind::Self_ => todo!("I think this should just be a panicky branch? or perhaps we want to get the assigned in tenv?"),
             TypeKind::Object(trid) => {
                 if let Some(impls) = variants.object.get(trid) {
@@ -149,6 +422,12 @@ impl<'a, D: TypeData> Selection<'a, D> {
                 }
                 self.filter_suitible(&variants.blanked, impltor,  &mut results, &mut contenders);
             }
+            TypeKind::Opaque(id) => {
+                if let Some(impls) = variants.opaque.get(id) {
+                    self.filter_suitible(impls, impltor, &mut results, &mut contenders);
+                }
+                self.filter_suitible(&variants.blanked, impltor,  &mut results, &mut contenders);
+            }
 
             TypeKind::Ref(rid) => match self.tenv.get_type(*rid) {
                     Some(impltor) => return self.run(impltor, variants),
@@ -205,10 +484,34 @@ impl<'a, D: TypeData> Selection<'a, D> {
             panic!("missmatched amount of trait parameters");
         }
 
-        let mut checker = TypeContext::new(&mut tenv, self.traits, ErrorHandler::Cheap);
+        let mut checker =
+            TypeContext::new(&mut tenv, self.traits, ErrorHandler::Cheap).with_kinds(self.kinds);
         checker
             .check_types(self.trait_params, &unified_trtp)
             .map_err(|_| Contender::InvalidTraitParams)?;
+        // An implementation of a higher-kinded trait for a partially applied type constructor
+        // such as `(result e)` is suitible for any application of it, so the parameters applied
+        // to the constructor are left out of the comparison.
+        let applied = self
+            .kinds
+            .trait_(self.trait_)
+            .map_or(0, |kind| kind.params());
+        let head;
+        let impltor = match &unified_impltor.constr {
+            TypeKind::Concrete(_) | TypeKind::Object(_)
+                if applied > 0 && impltor.params.len() > unified_impltor.params.len() =>
+            {
+                let at = impltor.params.len().saturating_sub(applied);
+                head = Type {
+                    constr: impltor.constr.clone(),
+                    meta: impltor.meta.clone(),
+                    params: impltor.params[..at].to_vec(),
+                };
+                &head
+            }
+            _ => impltor,
+        };
+
         checker
             .check(impltor, &unified_impltor)
             .map_err(|_| Contender::InvalidImpltor)?;
@@ -228,6 +531,7 @@ impl<D: TypeData> Variants<D> {
             default: None,
             concrete: HashMap::new(),
             object: HashMap::new(),
+            opaque: HashMap::new(),
             blanked: Vec::new(),
         }
     }
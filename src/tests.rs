@@ -1,6 +1,7 @@
 use super::*;
-use frontend::{ForeignFunction, ForeignTrait, Product, Sum};
+use frontend::{ForeignFunction, ForeignTrait, ImplFailure, ImplMethod, Product, Sum};
 use insta::assert_display_snapshot as snap;
+use itertools::Itertools;
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -11,6 +12,9 @@ impl TypeData for TestTypeData {
     type Generic = Generic;
     type Trait = &'static str;
     type Association = &'static str;
+    type Method = &'static str;
+    type Function = &'static str;
+    type Field = &'static str;
 
     type Meta = ();
 
@@ -99,33 +103,107 @@ impl fmt::Display for Generic {
 
 fn trait_from() -> ForeignTrait<TestTypeData> {
     let mut trait_ = ForeignTrait::with_capacity("From", forall!(a), 1);
-    trait_.push_method(func!( a => self_ ));
+    trait_.push_method("from", func!( a => self_ ));
     trait_
 }
 
 fn trait_into() -> ForeignTrait<TestTypeData> {
     let mut trait_ = ForeignTrait::with_capacity("Into", forall!(a), 1);
-    trait_.push_method(func!( self_ => a ));
+    trait_.push_method("into", func!( self_ => a ));
     trait_
 }
 
 fn trait_mixed_gen() -> ForeignTrait<TestTypeData> {
     let mut trait_ = ForeignTrait::with_capacity("HasGen", forall!(a), 1);
-    trait_.push_method(func!( forall b . (self_(), a(), b() => a()) ));
+    trait_.push_method("has_gen", func!( forall b . (self_(), a(), b() => a()) ));
+    trait_
+}
+
+fn trait_show() -> ForeignTrait<TestTypeData> {
+    let mut trait_ = ForeignTrait::with_capacity("Show", forall!(), 1);
+    trait_.push_method("show", func!( self_ => string ));
+    trait_
+}
+
+fn trait_debug() -> ForeignTrait<TestTypeData> {
+    let mut trait_ = ForeignTrait::with_capacity("Debug", forall!(), 1);
+    trait_.push_method("show", func!( self_ => string ));
+    trait_
+}
+
+fn trait_eq() -> ForeignTrait<TestTypeData> {
+    let mut trait_ = ForeignTrait::with_capacity("Eq", forall!(), 2);
+    trait_.push_method("eq", func!( self_, self_ => bool ));
+    trait_.push_default_method("ne", func!( self_, self_ => bool ), "ne_default");
     trait_
 }
 
 fn trait_index() -> TraitIndex<TestTypeData> {
     let mut index = TraitIndex::new();
-    index.implement(forall!(), "Intable", vec![], int(), vec![]);
-    index.implement(forall!(), "From", vec![int()], float(), vec![]);
-    index.implement(forall!( a ["From" b], b ), "Into", vec![a()], b(), vec![]);
+    index.implement(forall!(), "Intable", vec![], int(), vec![], vec![]);
+    index.implement(
+        forall!(),
+        "From",
+        vec![int()],
+        float(),
+        vec![Some("from_int")],
+        vec![],
+    );
+    index.implement(
+        forall!( a ["From" b], b ),
+        "Into",
+        vec![a()],
+        b(),
+        vec![Some("into_from")],
+        vec![],
+    );
     index.implement(
         forall!(a),
         "Functor",
         vec![],
         Type::concrete((), "option", vec![]),
         vec![],
+        vec![],
+    );
+    index.implement(
+        forall!(a),
+        "Functor",
+        vec![],
+        Type::concrete((), "result", vec![a()]),
+        vec![],
+        vec![],
+    );
+    index.implement(
+        forall!(a),
+        "Show",
+        vec![],
+        option([a()]),
+        vec![Some("show_option")],
+        vec![],
+    );
+    index.implement(
+        forall!(),
+        "Show",
+        vec![],
+        int(),
+        vec![Some("show_int")],
+        vec![],
+    );
+    index.implement(
+        forall!(),
+        "Debug",
+        vec![],
+        int(),
+        vec![Some("debug_int")],
+        vec![],
+    );
+    index.implement(
+        forall!(),
+        "Eq",
+        vec![],
+        int(),
+        vec![Some("eq_int"), None],
+        vec![],
     );
     index
 }
@@ -145,6 +223,12 @@ fn option<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData>
 fn int() -> Type<TestTypeData> {
     Type::concrete((), "int", vec![])
 }
+fn string() -> Type<TestTypeData> {
+    Type::concrete((), "string", vec![])
+}
+fn bool() -> Type<TestTypeData> {
+    Type::concrete((), "bool", vec![])
+}
 fn float() -> Type<TestTypeData> {
     Type::concrete((), "float", vec![])
 }
@@ -177,7 +261,7 @@ macro_rules! snap_f {
     ($f:expr, $params:expr) => {
         tenv(|tenv, traits| {
             let inst = $f.instantiate(tenv).function();
-            inst.clone().call(tenv, &traits, $params).unwrap();
+            inst.clone().call(tenv, &traits, &(), $params).unwrap();
             snap!(inst.to_foreign(&tenv, Generics::new()));
         })
     };
@@ -207,7 +291,7 @@ fn inst_recursive_query() {
     tenv(|tenv, traits| {
         let f = func!( forall a [ "From" a ]. (a => a) );
         let inst = f.instantiate(tenv).function();
-        inst.call(tenv, traits, &[int()]).unwrap();
+        inst.call(tenv, traits, &(), &[int()]).unwrap();
         let a = inst.to_foreign(tenv, Generics::new());
         println!("false positive:\n  {}", a);
     })
@@ -219,11 +303,86 @@ fn inst_functor() {
     snap_f!(map, &[option([int()])]);
 }
 
+#[test]
+fn inst_functor_partial_application() {
+    let map = func!( forall f ["Functor"]. (Type::generic((), gids::f, vec![int()]) => int()) );
+    snap_f!(map, &[result([string(), int()])]);
+}
+
+#[test]
+fn inst_functor_partial_query() {
+    tenv(|tenv, traits| {
+        let kinds = kinds();
+        let found = traits
+            .select(tenv, &kinds, "Functor", &[], &result([string(), int()]))
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0]
+            .tenv
+            .concretify_type(&found[0].unified_impltor)
+            .direct_eq(&Type::concrete((), "result", vec![string()])));
+
+        assert!(traits
+            .select(
+                tenv,
+                &kinds,
+                "Functor",
+                &[],
+                &Type::concrete((), "list", vec![int()])
+            )
+            .is_err());
+
+        // without a declared kind the trait is assumed to be implemented for fully applied types
+        assert!(traits
+            .select(tenv, &(), "Functor", &[], &result([string(), int()]))
+            .is_err());
+    })
+}
+
+#[test]
+fn inst_unapplied_query() {
+    let mut traits = trait_index();
+    let list = || Type::concrete((), "list", vec![]);
+    traits.implement(forall!(), "Show", vec![], list(), vec![], vec![]);
+
+    let tenv = TEnv::new();
+    assert!(traits.select(&tenv, &(), "Show", &[], &list()).is_ok());
+    assert!(traits
+        .select(
+            &tenv,
+            &(),
+            "Show",
+            &[],
+            &Type::concrete((), "list", vec![int()])
+        )
+        .is_err());
+}
+
+#[test]
+fn inst_functor_through_ref() {
+    tenv(|tenv, traits| {
+        let f = tenv.spawn_with_cons(vec![Constraint::new("Functor", vec![])]);
+        let b = tenv.spawn();
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(
+            &ref_of(f, vec![]),
+            &Type::concrete((), "result", vec![string()]),
+        )
+        .unwrap();
+        tctx.check(&ref_of(f, vec![int()]), &ref_of(b, vec![]))
+            .unwrap();
+        assert!(tenv
+            .concretify_type(&ref_of(b, vec![]))
+            .direct_eq(&result([string(), int()])));
+    })
+}
+
 #[test]
 fn inst_into_method() {
     tenv(|tenv, traits| {
         let inst = trait_into().instantiate(tenv).method(0, tenv);
-        inst.call(tenv, traits, &[int()]).unwrap();
+        inst.call(tenv, traits, &(), &[int()]).unwrap();
         snap!(inst.to_foreign(tenv, Generics::new()));
 
         let into = trait_into();
@@ -231,7 +390,7 @@ fn inst_into_method() {
         inst.set_self_check_constraint(tenv, traits, float())
             .unwrap();
         let minst = inst.method(0, tenv);
-        minst.call(tenv, traits, &[float()]).unwrap();
+        minst.call(tenv, traits, &(), &[float()]).unwrap();
         snap!(minst.to_foreign(tenv, Generics::new()));
     });
 }
@@ -243,7 +402,7 @@ fn inst_from_method_infer_self() {
         //
         // although; I guess it could also just infer `r when From int` which will also be valid
         let inst = trait_from().instantiate(tenv).method(0, tenv);
-        inst.call(tenv, traits, &[int()]).unwrap();
+        inst.call(tenv, traits, &(), &[int()]).unwrap();
         snap!(inst.to_foreign(tenv, Generics::new()));
     })
 }
@@ -251,22 +410,99 @@ fn inst_from_method_infer_self() {
 #[test]
 fn inst_product_field() {
     tenv(|tenv, traits| {
-        let prod = Product::new("point", vec![a(), a()]).to_foreign(forall!(a));
+        let prod = Product::new("point", vec![("x", a()), ("y", a())])
+            .to_foreign(forall!(a))
+            .unwrap()
+            .0;
         let inst = prod.instantiate(tenv);
         TypeContext::new(tenv, traits, ErrorHandler::Expensive)
-            .check(&int(), &inst.field(1))
+            .check(&int(), &inst.field(&"y").unwrap())
             .unwrap();
         snap!(format!("{}\n{}", inst.to_type(()), tenv));
     })
 }
 
+#[test]
+fn inst_product_unknown_field() {
+    tenv(|tenv, _| {
+        let prod = Product::new("point", vec![("x", a()), ("y", a())])
+            .to_foreign(forall!(a))
+            .unwrap()
+            .0;
+        let inst = prod.instantiate(tenv);
+        assert!(inst.field(&"z").is_none());
+        let err = inst.getter((), &"z").unwrap_err();
+        assert!(inst.setter((), &"z").is_err());
+        snap!(format!("{}\n{}", prod, err));
+    })
+}
+
+#[test]
+fn inst_product_functions() {
+    tenv(|tenv, traits| {
+        let prod = Product::new("pair", vec![("fst", a()), ("snd", b())])
+            .to_foreign(forall!(a, b))
+            .unwrap()
+            .0;
+        let inst = prod.instantiate(tenv);
+
+        let constructor = inst.constructor(());
+        constructor
+            .call(tenv, traits, &(), &[int(), string()])
+            .unwrap();
+        assert!(matches!(
+            constructor.call(tenv, traits, &(), &[int()]),
+            Err(frontend::CallError::ParamCount { got: 1, exp: 2 })
+        ));
+
+        let product = inst.to_type(());
+        let fst = inst.getter((), &"fst").unwrap();
+        fst.call(tenv, traits, &(), std::slice::from_ref(&product))
+            .unwrap();
+
+        let snd = inst.setter((), &"snd").unwrap();
+        snd.call(tenv, traits, &(), &[product.clone(), string()])
+            .unwrap();
+        assert!(snd.call(tenv, traits, &(), &[product, int()]).is_err());
+
+        snap!(format!("{}\n{}\n{}", constructor, fst, snd));
+    })
+}
+
+#[test]
+fn decl_validation() {
+    let undeclared = Product::new("point", vec![("x", a()), ("y", b())]).to_foreign(forall!(a));
+    let self_ =
+        Product::new("point", vec![("x", self_()), ("y", ref_of(0, vec![]))]).to_foreign(forall!());
+    let (_, unused) = Product::new("point", vec![("x", int())])
+        .to_foreign(forall!(a))
+        .unwrap();
+    let existential = Sum::new("showable", vec![vec![b()], vec![b()]])
+        .with_existential(0, forall!(b))
+        .to_foreign(forall!());
+    let duplicate = Product::new("point", vec![("x", int()), ("x", float())]).to_foreign(forall!());
+
+    let errors = undeclared
+        .unwrap_err()
+        .into_iter()
+        .chain(self_.unwrap_err())
+        .chain(existential.unwrap_err())
+        .chain(duplicate.unwrap_err())
+        .map(|err| err.to_string());
+    let warnings = unused.iter().map(|warning| warning.to_string());
+    snap!(errors.chain(warnings).format("\n"));
+}
+
 #[test]
 fn inst_sum_variant() {
     tenv(|tenv, traits| {
-        let sum = Sum::new("option", vec![vec![], vec![a()]]).to_foreign(forall!(a));
+        let sum = Sum::new("option", vec![vec![], vec![a()]])
+            .to_foreign(forall!(a))
+            .unwrap()
+            .0;
         let inst = sum.instantiate(tenv);
-        inst.constructor((), 1)
-            .call(tenv, traits, &[int()])
+        inst.constructor(tenv, (), 1)
+            .call(tenv, traits, &(), &[int()])
             .unwrap();
         snap!(format!("{}\n{}", inst.to_type(()), tenv));
     })
@@ -279,7 +515,7 @@ fn impl_method_matches_trait() {
 
         let mut inst = trait_.instantiate(tenv);
         inst.mapping_mut()
-            .annotate_types(tenv, traits, &[a()])
+            .annotate_types(tenv, traits, &kinds(), &TypeDecls::new(), &[a()])
             .unwrap();
         inst.set_self(float(), tenv);
 
@@ -304,10 +540,1361 @@ fn impl_generate_typing() {
         let trait_ = trait_mixed_gen();
         let mut inst = trait_.instantiate(tenv);
         inst.mapping_mut()
-            .annotate_types(tenv, traits, &[int()])
+            .annotate_types(tenv, traits, &kinds(), &TypeDecls::new(), &[int()])
             .unwrap();
         inst.set_self(float(), tenv);
         let m = inst.generate_method_annotation(0, tenv);
         println!("{}\n{}", m, &tenv);
     })
 }
+
+#[test]
+fn method_by_name() {
+    tenv(|tenv, traits| {
+        let (show, debug) = (trait_show(), trait_debug());
+        let resolved = traits
+            .resolve_method(tenv, &(), &[&show, &debug], &"show", &option([int()]))
+            .unwrap();
+        assert_eq!(resolved.trid, "Show");
+        snap!(resolved.function.to_foreign(tenv, Generics::new()));
+    })
+}
+
+#[test]
+fn method_by_name_ambiguous() {
+    tenv(|tenv, traits| {
+        let (show, debug) = (trait_show(), trait_debug());
+        let err = traits
+            .resolve_method(tenv, &(), &[&show, &debug], &"show", &int())
+            .unwrap_err();
+        assert!(matches!(err, MethodLookupError::Ambiguous(trids) if trids == ["Show", "Debug"]));
+
+        let err = traits
+            .resolve_method(tenv, &(), &[&show], &"into", &int())
+            .unwrap_err();
+        assert!(matches!(err, MethodLookupError::NotFound));
+    })
+}
+
+#[test]
+fn method_by_name_overlapping() {
+    let mut traits = trait_index();
+    traits.implement(
+        forall!(a),
+        "Show",
+        vec![],
+        a(),
+        vec![Some("show_any")],
+        vec![],
+    );
+
+    let mut tenv = TEnv::new();
+    let show = trait_show();
+    let err = traits
+        .resolve_method(&mut tenv, &(), &[&show], &"show", &int())
+        .unwrap_err();
+    assert!(matches!(err, MethodLookupError::OverlappingImpls(trid) if trid == "Show"));
+}
+
+#[test]
+fn dispatch_to_impl() {
+    tenv(|tenv, traits| {
+        let show = trait_show();
+        let dispatch = traits
+            .dispatch(tenv, &(), &show, &[], &option([int()]), 0)
+            .unwrap();
+        assert_eq!(dispatch.function, "show_option");
+        snap!(dispatch.impl_params(()).iter().format(", "));
+
+        let err = traits
+            .dispatch(tenv, &(), &show, &[], &float(), 0)
+            .unwrap_err();
+        assert!(matches!(err, DispatchError::NotImplemented(_)));
+    })
+}
+
+#[test]
+fn dispatch_to_default() {
+    tenv(|tenv, traits| {
+        let eq = trait_eq();
+
+        let dispatch = traits.dispatch(tenv, &(), &eq, &[], &int(), 0).unwrap();
+        assert_eq!((dispatch.function, dispatch.defaulted), ("eq_int", false));
+
+        let dispatch = traits.dispatch(tenv, &(), &eq, &[], &int(), 1).unwrap();
+        assert_eq!(
+            (dispatch.function, dispatch.defaulted),
+            ("ne_default", true)
+        );
+
+        let coverage = eq.coverage(&vec![None, None]);
+        assert_eq!((coverage.missing, coverage.defaulted), (vec![0], vec![1]));
+    })
+}
+
+fn impl_method(
+    name: &'static str,
+    signature: ForeignFunction<TestTypeData>,
+) -> ImplMethod<TestTypeData> {
+    ImplMethod {
+        name,
+        function: name,
+        signature,
+    }
+}
+
+#[test]
+fn impl_block_registers() {
+    let mut traits = trait_index();
+    let tenv = TEnv::new();
+    let eq = trait_eq();
+
+    eq.implement(
+        &mut traits,
+        &kinds(),
+        &TypeDecls::new(),
+        forall!(),
+        vec![],
+        float(),
+        vec![impl_method(
+            "eq",
+            func!( float(), Type::hole((), vec![]) => bool() ),
+        )],
+    )
+    .unwrap();
+
+    let dispatch = traits.dispatch(&tenv, &(), &eq, &[], &float(), 1).unwrap();
+    assert_eq!(dispatch.function, "ne_default");
+
+    // the signature is stored with its holes filled in
+    let signatures = &dispatch.impl_.signatures;
+    assert_eq!(
+        signatures[0].as_ref().unwrap().to_string(),
+        "(float, float -> bool)"
+    );
+    assert!(signatures[1].is_none());
+}
+
+#[test]
+fn impl_block_failures() {
+    let mut traits = trait_index();
+
+    let failures = trait_eq()
+        .implement(
+            &mut traits,
+            &kinds(),
+            &TypeDecls::new(),
+            forall!(),
+            vec![],
+            float(),
+            vec![
+                impl_method("ne", func!( float => bool )),
+                impl_method("neq", func!( float, float => bool )),
+            ],
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        failures.as_slice(),
+        [
+            ImplFailure::Arity {
+                method: "ne",
+                got: 1,
+                exp: 2
+            },
+            ImplFailure::Unknown("neq"),
+            ImplFailure::Missing("eq"),
+        ]
+    ));
+
+    let failures = trait_from()
+        .implement(
+            &mut traits,
+            &kinds(),
+            &TypeDecls::new(),
+            forall!(),
+            vec![int()],
+            string(),
+            vec![impl_method("from", func!( float => string ))],
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        failures.as_slice(),
+        [ImplFailure::Header("from", _)]
+    ));
+
+    let tenv = TEnv::new();
+    let err = traits.dispatch(&tenv, &(), &trait_from(), &[int()], &string(), 0);
+    assert!(err.is_err());
+
+    let mut generics = forall!();
+    generics.insert_with_kind(gids::f, Kind::arity(1));
+    let mut trait_ = ForeignTrait::new("Lift", generics);
+    trait_.push_method("lift", func!( self_() => f_of([self_()]) ));
+    let failures = trait_
+        .implement(
+            &mut traits,
+            &kinds(),
+            &TypeDecls::new(),
+            forall!(),
+            vec![int()],
+            string(),
+            vec![impl_method("lift", func!( string => int ))],
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        failures.as_slice(),
+        [ImplFailure::TraitParams(AnnotationError::Kind(..))]
+    ));
+}
+
+#[test]
+fn impl_generate_skeleton() {
+    let skeleton = trait_mixed_gen()
+        .generate_impl(&forall!(b), &[b()], &option([b()]))
+        .unwrap();
+    snap!(skeleton
+        .iter()
+        .format_with("\n", |(name, f), fmt| fmt(&format_args!("{}: {}", name, f))));
+}
+
+#[test]
+fn impl_generate_skeleton_param_count() {
+    let err = trait_mixed_gen()
+        .generate_impl(&forall!(), &[], &int())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ImplFailure::TraitParamCount { got: 0, exp: 1 }
+    ));
+}
+
+#[test]
+fn impl_generate_skeleton_keeps_kinds() {
+    let mut generics = forall!();
+    generics.insert_with_kind(gids::f, Kind::arity(1));
+    let mut signature = func!( f_of([int()]) => self_() );
+    signature.generics = generics;
+
+    let mut trait_ = ForeignTrait::new("Wrapped", forall!());
+    trait_.push_method("unwrap", signature);
+
+    // `f` is taken by the implementation block and renamed
+    let skeleton = trait_.generate_impl(&forall!(f), &[], &int()).unwrap();
+    let generics = &skeleton[0].1.generics;
+    assert_eq!(generics.kind(&gids::a), Some(&Kind::arity(1)));
+}
+
+#[test]
+fn impl_method_fill_holes() {
+    tenv(|tenv, traits| {
+        let trait_ = trait_mixed_gen();
+        let mut inst = trait_.instantiate(tenv);
+        inst.mapping_mut()
+            .annotate_types(tenv, traits, &kinds(), &TypeDecls::new(), &[int()])
+            .unwrap();
+        inst.set_self(float(), tenv);
+
+        let hole = || Type::hole((), vec![]);
+        let given = func!( float(), hole(), hole() => hole() );
+
+        let (completed, failures) = inst.verify_method_annotation(0, given, tenv);
+        assert!(failures.is_empty(), "{:#?}", failures);
+        snap!(completed);
+    })
+}
+
+#[test]
+fn impl_method_unassigned_ref() {
+    tenv(|tenv, _| {
+        let trait_ = trait_eq();
+        let inst = trait_.instantiate(tenv);
+        let elem = tenv.spawn();
+        inst.set_self(option([Type::reference((), elem, vec![])]), tenv);
+
+        let given = func!( option([int()]), option([int()]) => bool() );
+        let (_, failures) = inst.verify_method_annotation(0, given, tenv);
+        assert!(failures.is_empty(), "{:#?}", failures);
+
+        let given = func!( option([int()]), option([string()]) => bool() );
+        let (_, failures) = inst.verify_method_annotation(0, given, tenv);
+        assert!(matches!(
+            failures.as_slice(),
+            [ImplHeaderFailure::Missmatch { .. }]
+        ));
+    })
+}
+
+#[test]
+fn impl_method_ref_in_signature() {
+    tenv(|tenv, _| {
+        let trait_ = trait_eq();
+        let inst = trait_.instantiate(tenv);
+        inst.set_self(int(), tenv);
+
+        let rid = tenv.spawn();
+        let given = func!( int(), Type::reference((), rid, vec![]) => bool() );
+        let (_, failures) = inst.verify_method_annotation(0, given, tenv);
+        assert!(matches!(
+            failures.as_slice(),
+            [ImplHeaderFailure::RefInSignature(r)] if *r == rid
+        ));
+    })
+}
+
+#[test]
+fn function_with_holes() {
+    tenv(|tenv, traits| {
+        let hole = || Type::hole((), vec![]);
+        let f = func!( forall a. (a(), hole() => hole()) );
+
+        let first = f.instantiate(tenv).function();
+        first.call(tenv, traits, &(), &[int(), string()]).unwrap();
+        let second = f.instantiate(tenv).function();
+        second.call(tenv, traits, &(), &[int(), float()]).unwrap();
+
+        snap!(format!(
+            "{}\n{}\n{}",
+            f,
+            tenv.concretify_types(&first.ptypes).iter().format(", "),
+            tenv.concretify_types(&second.ptypes).iter().format(", "),
+        ));
+    })
+}
+
+fn f_of<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData> {
+    Type::generic((), gids::f, params.to_vec())
+}
+
+fn trait_functor() -> ForeignTrait<TestTypeData> {
+    let mut trait_ = ForeignTrait::with_capacity("Functor", forall!(), 1);
+    let self_a = Type::self_((), vec![a()]);
+    let self_b = Type::self_((), vec![b()]);
+    trait_.push_method("map", func!( forall a, b. (self_a, b() => self_b) ));
+    trait_
+}
+
+#[test]
+fn kind_infer_function() {
+    let f = func!( forall f, a. (f_of([a()]), a() => f_of([option([a()])])) );
+    let kinds = f.infer_kinds(&()).unwrap();
+    snap!(kinds
+        .iter()
+        .format_with(", ", |(gid, kind), fmt| fmt(&format_args!(
+            "{}: {}",
+            gid, kind
+        ))));
+}
+
+#[test]
+fn kind_infer_trait() {
+    let kinds = trait_functor().infer_kinds(&()).unwrap();
+    assert_eq!(kinds.self_, Kind::arity(1));
+    assert_eq!(
+        kinds.methods,
+        vec![vec![(gids::a, Kind::Type), (gids::b, Kind::Type)]]
+    );
+}
+
+#[test]
+fn kind_declared_missmatch() {
+    let mut kinds = Kinds::new();
+    kinds.declare_concrete("option", Kind::arity(1));
+
+    let f = func!( forall a. (option([]) => a()) );
+    match f.infer_kinds(&kinds) {
+        Err(KindError::Missmatch { expected, got, .. }) => {
+            assert_eq!(expected, Kind::Type);
+            assert_eq!(got, Kind::arity(1));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    let mut annotated = func!( forall f. (f_of([int()]) => int()) );
+    annotated.generics.set_kind(gids::f, Kind::Type);
+    assert!(annotated.infer_kinds(&kinds).is_err());
+
+    let infinite = func!( forall f. (f_of([f_of([])]) => int()) );
+    assert!(matches!(
+        infinite.infer_kinds(&()),
+        Err(KindError::Infinite(_))
+    ));
+}
+
+#[test]
+fn kind_missmatch_on_call() {
+    tenv(|tenv, traits| {
+        let map = func!( forall f ["Functor"]. (f_of([int()]) => int()) );
+        let inst = map.instantiate(tenv).function();
+        match inst.call(tenv, traits, &(), &[int()]) {
+            Err(frontend::CallError::CheckErrors(errors)) => {
+                assert!(matches!(&errors[0].1, Error::KindMissmatch { .. }))
+            }
+            other => panic!("{:?}", other),
+        }
+    })
+}
+
+fn result<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData> {
+    Type::concrete((), "result", params.to_vec())
+}
+
+fn ref_of(rid: RefID, params: TypesBuf<TestTypeData>) -> Type<TestTypeData> {
+    Type::reference((), rid, params)
+}
+
+#[test]
+fn hkt_unify_partial_refs() {
+    tenv(|tenv, traits| {
+        let (f, a, b) = (tenv.spawn(), tenv.spawn(), tenv.spawn());
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&ref_of(f, vec![ref_of(a, vec![])]), &ref_of(b, vec![]))
+            .unwrap();
+        tctx.check(&ref_of(b, vec![]), &result([string(), int()]))
+            .unwrap();
+
+        let inferred = [ref_of(f, vec![]), ref_of(a, vec![])];
+        snap!(tenv.concretify_types(&inferred).iter().format(", "));
+    })
+}
+
+#[test]
+fn hkt_unify_partial_refs_functor() {
+    tenv(|tenv, traits| {
+        let f = tenv.spawn_with_cons(vec![Constraint::new("Functor", vec![])]);
+        let (a, b) = (tenv.spawn(), tenv.spawn());
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&ref_of(b, vec![]), &ref_of(f, vec![ref_of(a, vec![])]))
+            .unwrap();
+        tctx.check(&option([int()]), &ref_of(b, vec![])).unwrap();
+
+        assert!(tenv
+            .concretify_type(&ref_of(f, vec![]))
+            .direct_eq(&Type::concrete((), "option", vec![])));
+        assert!(tenv.concretify_type(&ref_of(a, vec![])).direct_eq(&int()));
+    })
+}
+
+#[test]
+fn hkt_unify_partial_refs_constrained() {
+    tenv(|tenv, traits| {
+        let (f, a) = (tenv.spawn(), tenv.spawn());
+        let b = tenv.spawn_with_cons(vec![Constraint::new("Show", vec![])]);
+
+        // `show (pure x)`, the constraint on `'b` is checked once `'f` is known
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&ref_of(f, vec![ref_of(a, vec![])]), &ref_of(b, vec![]))
+            .unwrap();
+        tctx.check(&option([int()]), &ref_of(b, vec![])).unwrap();
+
+        let (f, a) = (tenv.spawn(), tenv.spawn());
+        let b = tenv.spawn_with_cons(vec![Constraint::new("Show", vec![])]);
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&ref_of(f, vec![ref_of(a, vec![])]), &ref_of(b, vec![]))
+            .unwrap();
+        let err = tctx
+            .check(&result([string(), int()]), &ref_of(b, vec![]))
+            .unwrap_err();
+        assert!(matches!(err, Error::ConstraintNotMet(_, con, _) if con.trid == "Show"));
+    })
+}
+
+#[test]
+fn hkt_unify_partial_refs_functor_result() {
+    tenv(|tenv, traits| {
+        let f = tenv.spawn_with_cons(vec![Constraint::new("Functor", vec![])]);
+        let (a, b) = (tenv.spawn(), tenv.spawn());
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&ref_of(b, vec![]), &ref_of(f, vec![ref_of(a, vec![])]))
+            .unwrap();
+        tctx.check(&result([string(), int()]), &ref_of(b, vec![]))
+            .unwrap();
+
+        let inferred = [ref_of(f, vec![]), ref_of(a, vec![])];
+        snap!(tenv.concretify_types(&inferred).iter().format(", "));
+    })
+}
+
+fn kinds() -> Kinds<TestTypeData> {
+    let mut kinds = Kinds::new();
+    kinds.declare_concrete("int", Kind::Type);
+    kinds.declare_concrete("option", Kind::arity(1));
+    kinds.declare_concrete("list", Kind::arity(1));
+    kinds.declare_concrete("result", Kind::arity(2));
+    kinds.declare_trait("Functor", Kind::arity(1));
+    kinds
+}
+
+#[test]
+fn annotate_higher_kinded() {
+    tenv(|tenv, traits| {
+        let map = func!( forall f ["Functor"]. (f_of([int()]) => f_of([string()])) )
+            .with_kinds(&kinds())
+            .unwrap();
+        let mut inst = map.instantiate(tenv);
+        inst.mapping_mut()
+            .annotate_gid(
+                tenv,
+                traits,
+                &kinds(),
+                &TypeDecls::new(),
+                &gids::f,
+                option([]),
+            )
+            .unwrap();
+
+        let f = inst.function();
+        assert!(tenv
+            .concretify_type(&f.ptypes[0])
+            .direct_eq(&option([int()])));
+
+        let mut inst = map.instantiate(tenv);
+        inst.mapping_mut()
+            .annotate_gid(
+                tenv,
+                traits,
+                &kinds(),
+                &TypeDecls::new(),
+                &gids::f,
+                result([string()]),
+            )
+            .unwrap();
+        let f = inst.function();
+        snap!(tenv.concretify_type(&f.returns));
+    })
+}
+
+#[test]
+fn annotate_kinds_without_declaration() {
+    tenv(|tenv, traits| {
+        // the kinds are infered on instantiation when the declaration didn't provide them
+        let map = func!( forall f. (f_of([int()]) => int()) );
+
+        let mut inst = map.instantiate(tenv);
+        let err = inst
+            .mapping_mut()
+            .annotate_gid(tenv, traits, &kinds(), &TypeDecls::new(), &gids::f, int())
+            .unwrap_err();
+        assert!(matches!(err, AnnotationError::Kind(_)), "{:?}", err);
+
+        let mut inst = map.instantiate(tenv);
+        inst.mapping_mut()
+            .annotate_gid(
+                tenv,
+                traits,
+                &kinds(),
+                &TypeDecls::new(),
+                &gids::f,
+                option([]),
+            )
+            .unwrap();
+    })
+}
+
+#[test]
+fn annotate_higher_kinded_failures() {
+    tenv(|tenv, traits| {
+        let map = func!( forall f ["Functor"]. (f_of([int()]) => int()) )
+            .with_kinds(&kinds())
+            .unwrap();
+
+        for annotation in [int(), option([int()]), result([])] {
+            let mut inst = map.instantiate(tenv);
+            let err = inst
+                .mapping_mut()
+                .annotate_gid(
+                    tenv,
+                    traits,
+                    &kinds(),
+                    &TypeDecls::new(),
+                    &gids::f,
+                    annotation,
+                )
+                .unwrap_err();
+            assert!(matches!(err, AnnotationError::Kind(_)), "{:?}", err);
+        }
+
+        let mut inst = map.instantiate(tenv);
+        let err = inst
+            .mapping_mut()
+            .annotate_gid(
+                tenv,
+                traits,
+                &kinds(),
+                &TypeDecls::new(),
+                &gids::f,
+                Type::concrete((), "list", vec![]),
+            )
+            .unwrap_err();
+        assert!(matches!(err, AnnotationError::Constraint(..)));
+
+        let trait_ = trait_from();
+        let mut inst = trait_.instantiate(tenv);
+        let err = inst
+            .mapping_mut()
+            .annotate_types(tenv, traits, &kinds(), &TypeDecls::new(), &[int(), int()])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AnnotationError::ParamCount { got: 2, exp: 1 }
+        ));
+    })
+}
+
+#[test]
+fn generalize_let_binding() {
+    tenv(|tenv, traits| {
+        // the type of a lambda parameter in the enclosing scope, partially infered
+        let (param, inner) = (tenv.spawn(), tenv.spawn());
+        tenv.assign(param, option([ref_of(inner, vec![])]));
+        let (x, y) = (tenv.spawn(), tenv.spawn());
+
+        let binding = Function::new(
+            vec![
+                ref_of(param, vec![]),
+                ref_of(inner, vec![]),
+                ref_of(x, vec![]),
+            ],
+            ref_of(y, vec![]),
+        );
+        let generalized = binding.generalize(tenv, Generics::new(), &[param]);
+        snap!(generalized);
+
+        let first = generalized.instantiate(tenv).function();
+        first
+            .call(tenv, traits, &(), &[option([int()]), int(), string()])
+            .unwrap();
+
+        let second = generalized.instantiate(tenv).function();
+        assert!(second
+            .call(tenv, traits, &(), &[option([float()]), float(), float()])
+            .is_err());
+
+        let third = generalized.instantiate(tenv).function();
+        third
+            .call(tenv, traits, &(), &[option([int()]), int(), float()])
+            .unwrap();
+    })
+}
+
+#[test]
+fn recursive_group() {
+    tenv(|tenv, traits| {
+        let group = frontend::RecursiveGroup::spawn(tenv, &[1, 1, 1], ());
+
+        // f x = g x
+        let f = group.enter(tenv, 0);
+        let g = group.call(tenv, 1);
+        g.call(tenv, traits, &(), &f.ptypes).unwrap();
+        TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&g.returns, &f.returns)
+            .unwrap();
+        tenv.leave_scope();
+
+        // g y = f y
+        let g = group.enter(tenv, 1);
+        let f = group.call(tenv, 0);
+        f.call(tenv, traits, &(), &g.ptypes).unwrap();
+        tenv.leave_scope();
+
+        // h z = show z
+        let h = group.enter(tenv, 2);
+        let show = func!( forall a ["Show"]. (a => string) );
+        let show = show.instantiate(tenv).function();
+        show.call(tenv, traits, &(), &h.ptypes).unwrap();
+        TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&show.returns, &h.returns)
+            .unwrap();
+        tenv.leave_scope();
+
+        let generalized = group.generalize(tenv, &[]);
+        snap!(generalized.iter().format("\n"));
+    })
+}
+
+fn nested<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData> {
+    Type::concrete((), "nested", params.to_vec())
+}
+fn list<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData> {
+    Type::concrete((), "list", params.to_vec())
+}
+
+#[test]
+fn recursive_group_polymorphic_recursion() {
+    tenv(|tenv, traits| {
+        let depth = func!( forall a. (nested([a()]) => int()) );
+        let members = vec![
+            frontend::GroupMember::Declared(depth),
+            frontend::GroupMember::Inferred { arity: 1 },
+        ];
+        let group = frontend::RecursiveGroup::new(tenv, members, ());
+
+        // depth x = 1 + depth (unnest x)
+        let sig = group.enter(tenv, 0);
+        let unnest = func!( forall a. (nested([a()]) => nested([list([a()])])) );
+        let unnest = unnest.instantiate(tenv).function();
+        unnest.call(tenv, traits, &(), &sig.ptypes).unwrap();
+
+        let recursive = group.call(tenv, 0);
+        recursive
+            .call(tenv, traits, &(), &[unnest.returns])
+            .unwrap();
+        TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&recursive.returns, &sig.returns)
+            .unwrap();
+        tenv.leave_scope();
+
+        // top y = depth y
+        let top = group.enter(tenv, 1);
+        let depth = group.call(tenv, 0);
+        depth.call(tenv, traits, &(), &top.ptypes).unwrap();
+        TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&depth.returns, &top.returns)
+            .unwrap();
+        tenv.leave_scope();
+
+        snap!(group.generalize(tenv, &[]).iter().format("\n"));
+    })
+}
+
+#[test]
+fn recursive_group_rigid_signature() {
+    tenv(|tenv, traits| {
+        let id = func!( forall a. (a => a) );
+        let group =
+            frontend::RecursiveGroup::new(tenv, vec![frontend::GroupMember::Declared(id)], ());
+
+        // id x = 0
+        let sig = group.enter(tenv, 0);
+        assert!(TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&int(), &sig.returns)
+            .is_err());
+        tenv.leave_scope();
+    })
+}
+
+#[test]
+fn skolem_escape() {
+    tenv(|tenv, traits| {
+        let outer = tenv.spawn();
+
+        let id = func!( forall a. (a => a) );
+        let sig = id.skolemize(tenv);
+        let inner = tenv.spawn();
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&sig.ptypes[0], &ref_of(inner, vec![])).unwrap();
+        let err = tctx
+            .check(&sig.returns, &ref_of(outer, vec![]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::EscapingGeneric { generic: Generic(0), into } if into == outer
+        ));
+        tenv.leave_scope();
+    })
+}
+
+#[test]
+fn skolem_escape_through_inner_ref() {
+    tenv(|tenv, traits| {
+        let outer = tenv.spawn();
+
+        let id = func!( forall a. (a => a) );
+        let sig = id.skolemize(tenv);
+        let inner = tenv.spawn();
+
+        // the inner reference is unified with the outer one, so it now lives in the outer scope
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&ref_of(inner, vec![]), &ref_of(outer, vec![]))
+            .unwrap();
+        let err = tctx
+            .check(&sig.ptypes[0], &ref_of(inner, vec![]))
+            .unwrap_err();
+        assert!(matches!(err, Error::EscapingGeneric { .. }));
+        tenv.leave_scope();
+    })
+}
+
+#[test]
+fn skolem_assumptions() {
+    tenv(|tenv, traits| {
+        let show = func!( forall b ["Show"]. (b => string) );
+
+        // show_twice x = show x
+        let declared = func!( forall a ["Show"]. (a => string) );
+        let sig = declared.skolemize(tenv);
+        let inst = show.instantiate(tenv).function();
+        inst.call(tenv, traits, &(), &sig.ptypes).unwrap();
+        tenv.leave_scope();
+
+        let undeclared = func!( forall a. (a => string) );
+        let sig = undeclared.skolemize(tenv);
+        let inst = show.instantiate(tenv).function();
+        match inst.call(tenv, traits, &(), &sig.ptypes) {
+            Err(frontend::CallError::CheckErrors(errors)) => {
+                assert!(matches!(errors[0].1, Error::ConstraintNotMet(..)))
+            }
+            other => panic!("{:?}", other),
+        }
+        tenv.leave_scope();
+    })
+}
+
+#[test]
+fn skolem_assumptions_of_same_trait() {
+    tenv(|tenv, traits| {
+        let from_float = func!( forall b ["From" float]. (b => string) );
+
+        // f x = from_float x
+        let declared = func!( forall a ["From" int, "From" float]. (a => string) );
+        let sig = declared.skolemize(tenv);
+        let inst = from_float.instantiate(tenv).function();
+        inst.call(tenv, traits, &(), &sig.ptypes).unwrap();
+        tenv.leave_scope();
+    })
+}
+
+#[test]
+fn skolem_shadowed_generics() {
+    tenv(|tenv, traits| {
+        let outer = func!( forall a. (a => a) ).skolemize(tenv);
+        let inner = func!( forall a. (int => a) ).skolemize(tenv);
+
+        // the `a` of the inner scope is a different type than the `a` of the outer one
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&outer.ptypes[0], &outer.returns).unwrap();
+        let err = tctx.check(&outer.ptypes[0], &inner.returns).unwrap_err();
+        assert!(matches!(err, Error::Missmatch { .. }));
+
+        tenv.leave_scope();
+        tenv.leave_scope();
+    })
+}
+
+fn fn_of<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData> {
+    Type::concrete((), "fn", params.to_vec())
+}
+
+fn rank2() -> ForeignFunction<TestTypeData> {
+    let id = Type::forall((), vec![gids::a], fn_of([a(), a()]));
+    func!( fn_of([id, int()]) => int() )
+}
+
+#[test]
+fn rank_n_display() {
+    snap!(rank2());
+}
+
+#[test]
+fn rank_n_argument() {
+    tenv(|tenv, traits| {
+        let f = rank2().instantiate(tenv).function();
+
+        let polymorphic = Type::forall((), vec![gids::b], fn_of([b(), b()]));
+        f.call(tenv, traits, &(), &[fn_of([polymorphic, int()])])
+            .unwrap();
+
+        let monomorphic = fn_of([int(), int()]);
+        assert!(f
+            .call(tenv, traits, &(), &[fn_of([monomorphic, int()])])
+            .is_err());
+
+        let inferred = fn_of([tenv.spawn_type(()), tenv.spawn_type(())]);
+        let err = f.call(tenv, traits, &(), &[fn_of([inferred, int()])]);
+        assert!(err.is_err());
+    })
+}
+
+#[test]
+fn rank_n_instantiate_on_use() {
+    tenv(|tenv, traits| {
+        let sig = rank2().skolemize(tenv);
+        let id = match &sig.ptypes[0].params[0] {
+            t @ Type {
+                constr: TypeKind::Forall(_),
+                ..
+            } => t.clone(),
+            other => panic!("{}", other),
+        };
+
+        // (id 1, id "")
+        let (x, y) = (tenv.spawn(), tenv.spawn());
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&id, &fn_of([int(), ref_of(x, vec![])])).unwrap();
+        tctx.check(&id, &fn_of([string(), ref_of(y, vec![])]))
+            .unwrap();
+        tenv.leave_scope();
+
+        snap!(tenv
+            .concretify_types(&[ref_of(x, vec![]), ref_of(y, vec![])])
+            .iter()
+            .format(", "));
+    })
+}
+
+#[test]
+fn opaque_return_type() {
+    let mut traits = trait_index();
+    let mut opaque = frontend::ForeignOpaque::declare(
+        &mut traits,
+        forall!(a),
+        vec![Constraint::new("Show", vec![])],
+    );
+    let f = func!( forall a. (a() => opaque.to_type(())) );
+
+    let mut tenv = TEnv::new();
+
+    // defining function: f x = some x
+    let (scope, sig) = f.skolemize_scoped(&mut tenv);
+    let hidden = opaque.spawn_hidden(&mut tenv, &scope, ());
+    TypeContext::new(&mut tenv, &traits, ErrorHandler::Expensive)
+        .check(&option([sig.ptypes[0].clone()]), &hidden)
+        .unwrap();
+    tenv.leave_scope();
+    opaque.define(&tenv, &hidden).unwrap();
+
+    // caller: show (f 1)
+    let inst = f.instantiate(&mut tenv).function();
+    inst.call(&mut tenv, &traits, &(), &[int()]).unwrap();
+
+    let show = func!( forall b ["Show"]. (b => string) );
+    let show = show.instantiate(&mut tenv).function();
+    show.call(&mut tenv, &traits, &(), std::slice::from_ref(&inst.returns))
+        .unwrap();
+
+    let debug = func!( forall b ["Debug"]. (b => string) );
+    let debug = debug.instantiate(&mut tenv).function();
+    assert!(debug
+        .call(&mut tenv, &traits, &(), std::slice::from_ref(&inst.returns))
+        .is_err());
+
+    assert!(
+        TypeContext::new(&mut tenv, &traits, ErrorHandler::Expensive)
+            .check(&inst.returns, &option([int()]))
+            .is_err()
+    );
+
+    let returned = tenv.concretify_type(&inst.returns);
+    snap!(format!(
+        "{}\n{} = {}",
+        f,
+        returned,
+        opaque.reveal(&returned)
+    ));
+}
+
+#[test]
+fn opaque_hidden_type_must_meet_constraints() {
+    let mut traits = trait_index();
+    let opaque = frontend::ForeignOpaque::declare(
+        &mut traits,
+        forall!(),
+        vec![Constraint::new("Show", vec![])],
+    );
+
+    let mut tenv = TEnv::new();
+    let scope = tenv.enter_scope(&forall!());
+    let hidden = opaque.spawn_hidden(&mut tenv, &scope, ());
+    let err = TypeContext::new(&mut tenv, &traits, ErrorHandler::Expensive)
+        .check(&string(), &hidden)
+        .unwrap_err();
+    assert!(matches!(err, Error::ConstraintNotMet(..)));
+}
+
+#[test]
+fn opaque_constraint_over_function_generics() {
+    let mut traits = trait_index();
+    traits.implement(forall!(a), "Wrap", vec![a()], option([a()]), vec![], vec![]);
+    let mut opaque = frontend::ForeignOpaque::declare(
+        &mut traits,
+        forall!(a),
+        vec![Constraint::new("Wrap", vec![a()])],
+    );
+    let f = func!( forall a. (a() => opaque.to_type(())) );
+
+    let mut tenv = TEnv::new();
+
+    // defining function: f x = some x
+    let (scope, sig) = f.skolemize_scoped(&mut tenv);
+    let hidden = opaque.spawn_hidden(&mut tenv, &scope, ());
+    TypeContext::new(&mut tenv, &traits, ErrorHandler::Expensive)
+        .check(&option([sig.ptypes[0].clone()]), &hidden)
+        .unwrap();
+    tenv.leave_scope();
+    opaque.define(&tenv, &hidden).unwrap();
+
+    // returning the wrong wrapper is rejected
+    let scope = tenv.enter_scope(&forall!(a));
+    let hidden = opaque.spawn_hidden(&mut tenv, &scope, ());
+    let err = TypeContext::new(&mut tenv, &traits, ErrorHandler::Expensive)
+        .check(&option([int()]), &hidden)
+        .unwrap_err();
+    assert!(matches!(err, Error::ConstraintNotMet(..)));
+    tenv.leave_scope();
+
+    snap!(format!("{}\n{}", f, opaque.hidden().unwrap()));
+}
+
+fn showable() -> frontend::ForeignSum<TestTypeData> {
+    Sum::new("showable", vec![vec![b()], vec![]])
+        .with_existential(0, forall!(b["Show"]))
+        .to_foreign(forall!())
+        .unwrap()
+        .0
+}
+
+#[test]
+fn existential_variant() {
+    tenv(|tenv, traits| {
+        let sum = showable();
+        let inst = sum.instantiate(tenv);
+
+        inst.constructor(tenv, (), 0)
+            .call(tenv, traits, &(), &[int()])
+            .unwrap();
+        assert!(inst
+            .constructor(tenv, (), 0)
+            .call(tenv, traits, &(), &[string()])
+            .is_err());
+
+        snap!(sum);
+    })
+}
+
+#[test]
+fn existential_destruct() {
+    tenv(|tenv, traits| {
+        let outer = tenv.spawn();
+        let sum = showable();
+        let inst = sum.instantiate(tenv);
+
+        let params = inst.destruct(tenv, 0);
+
+        let show = func!( forall a ["Show"]. (a => string) );
+        let show = show.instantiate(tenv).function();
+        show.call(tenv, traits, &(), &params).unwrap();
+
+        let debug = func!( forall a ["Debug"]. (a => string) );
+        let debug = debug.instantiate(tenv).function();
+        assert!(debug.call(tenv, traits, &(), &params).is_err());
+
+        let err = TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&params[0], &ref_of(outer, vec![]))
+            .unwrap_err();
+        assert!(matches!(err, Error::EscapingGeneric { .. }));
+        tenv.leave_scope();
+    })
+}
+
+#[test]
+fn existential_destruct_twice() {
+    tenv(|tenv, traits| {
+        let sum = showable();
+        let inst = sum.instantiate(tenv);
+
+        // each unpacked value hides its own type, so they may not be used interchangeably
+        let x = inst.destruct(tenv, 0);
+        let y = inst.destruct(tenv, 0);
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive);
+        tctx.check(&x[0], &x[0]).unwrap();
+        let err = tctx.check(&x[0], &y[0]).unwrap_err();
+        assert!(matches!(err, Error::Missmatch { .. }));
+
+        tenv.leave_scope();
+        tenv.leave_scope();
+    })
+}
+
+fn type_decls() -> TypeDecls<TestTypeData> {
+    let mut decls = TypeDecls::new();
+    let declare_product = |decls: &mut TypeDecls<_>, name, fields, generics| {
+        let (product, _) = Product::new(name, fields).to_foreign(generics).unwrap();
+        decls.declare_product(product);
+    };
+    declare_product(
+        &mut decls,
+        "list",
+        vec![("head", a()), ("tail", list([a()]))],
+        forall!(a),
+    );
+    declare_product(
+        &mut decls,
+        "set",
+        vec![("elems", list([a()]))],
+        forall!(a["Show"]),
+    );
+    let (result, _) = Sum::new("result", vec![vec![a()], vec![b()]])
+        .to_foreign(forall!(a, b))
+        .unwrap();
+    decls.declare_sum(result);
+    decls
+}
+
+#[test]
+fn type_decls_arity() {
+    tenv(|tenv, traits| {
+        let decls = type_decls();
+        assert_eq!(decls.concrete(&"list"), Some(Kind::arity(1)));
+        assert_eq!(decls.concrete(&"result"), Some(Kind::arity(2)));
+        assert!(decls.product(&"list").is_some());
+        assert!(decls.sum(&"list").is_none());
+
+        let x = tenv.spawn();
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive).with_decls(&decls);
+        tctx.check(&list([int()]), &list([int()])).unwrap();
+
+        let err = tctx
+            .check(&list([int(), int()]), &list([int(), int()]))
+            .unwrap_err();
+        assert!(matches!(err, Error::ArityMissmatch { expected: 1, .. }));
+
+        let err = tctx
+            .check(&option([result([int(), int(), int()])]), &ref_of(x, vec![]))
+            .unwrap_err();
+        assert!(matches!(err, Error::ArityMissmatch { expected: 2, .. }));
+
+        // partially applied to a higher-kinded reference
+        let f = tenv.spawn();
+        TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .with_decls(&decls)
+            .check(&result([string(), int()]), &ref_of(f, vec![int()]))
+            .unwrap();
+    })
+}
+
+fn set<const N: usize>(params: [Type<TestTypeData>; N]) -> Type<TestTypeData> {
+    Type::concrete((), "set", params.to_vec())
+}
+
+#[test]
+fn well_formed_types() {
+    tenv(|tenv, traits| {
+        let decls = type_decls();
+        decls.well_formed(tenv, traits, &set([int()])).unwrap();
+
+        let errors = decls
+            .well_formed(tenv, traits, &list([set([float()])]))
+            .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [WfError::Unsatisfied { type_, param: 0, error: Error::ConstraintNotMet(..) }]
+                if type_.to_string() == "(set float)"
+        ));
+        // checking the constraints doesn't leave references behind
+        assert!(tenv.is_empty());
+
+        let errors = decls
+            .well_formed(tenv, traits, &set([int(), int()]))
+            .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [WfError::Arity(Error::ArityMissmatch { expected: 1, .. })]
+        ));
+
+        // the constraint is carried over to the reference for once it's infered
+        let x = tenv.spawn();
+        decls
+            .well_formed(tenv, traits, &set([ref_of(x, vec![])]))
+            .unwrap();
+        assert!(TypeContext::new(tenv, traits, ErrorHandler::Expensive)
+            .check(&float(), &ref_of(x, vec![]))
+            .is_err());
+    })
+}
+
+#[test]
+fn well_formed_signature() {
+    tenv(|tenv, traits| {
+        let decls = type_decls();
+
+        let constrained = func!( forall a ["Show"]. (set([a()]) => int()) );
+        decls
+            .well_formed_function(tenv, traits, &constrained)
+            .unwrap();
+
+        let unconstrained = func!( forall a. (set([a()]) => int()) );
+        let errors = decls
+            .well_formed_function(tenv, traits, &unconstrained)
+            .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [WfError::Unsatisfied { param: 0, .. }]
+        ));
+        assert_eq!(tenv.level(), 0);
+    })
+}
+
+#[test]
+fn well_formed_annotations() {
+    tenv(|tenv, traits| {
+        let decls = type_decls();
+        let f = func!( forall a. (a => int) );
+
+        let annotate = |tenv: &mut TEnv<_>, t| {
+            f.instantiate(tenv)
+                .mapping_mut()
+                .annotate_gid(tenv, traits, &(), &decls, &gids::a, t)
+        };
+
+        annotate(tenv, set([int()])).unwrap();
+
+        let err = annotate(tenv, set([float()])).unwrap_err();
+        assert!(matches!(
+            &err,
+            AnnotationError::WellFormed(errors)
+                if matches!(&errors[..], [WfError::Unsatisfied { param: 0, .. }])
+        ));
+
+        let err = annotate(tenv, list([int(), int()])).unwrap_err();
+        assert!(matches!(
+            &err,
+            AnnotationError::WellFormed(errors) if matches!(&errors[..], [WfError::Arity(_)])
+        ));
+    })
+}
+
+#[test]
+fn implied_bounds() {
+    tenv(|tenv, traits| {
+        let decls = type_decls();
+        let insert = func!( forall a. (set([a()]), a() => set([a()])) );
+        let implied = decls.with_implied_bounds(&insert);
+
+        // insert s x = show x
+        let show = func!( forall b ["Show"]. (b => string) );
+        let sig = implied.skolemize(tenv);
+        let inst = show.instantiate(tenv).function();
+        inst.call(tenv, traits, &(), &sig.ptypes[1..]).unwrap();
+        tenv.leave_scope();
+
+        let inst = implied.instantiate(tenv).function();
+        assert!(inst
+            .call(tenv, traits, &(), &[set([float()]), float()])
+            .is_err());
+
+        snap!(implied);
+    })
+}
+
+#[test]
+fn variance_of_decls() {
+    let mut decls = type_decls();
+    decls.declare_variance("fn", vec![Variance::Contravariant, Variance::Covariant]);
+
+    let products = [
+        ("sink", vec![("put", fn_of([a(), int()]))]),
+        (
+            "cell",
+            vec![("get", fn_of([int(), a()])), ("set", fn_of([a(), int()]))],
+        ),
+        ("phantom", vec![("x", int())]),
+        (
+            "stream",
+            vec![(
+                "next",
+                Type::concrete((), "sink", vec![Type::concrete((), "sink", vec![a()])]),
+            )],
+        ),
+    ];
+    for (name, fields) in products.iter().cloned() {
+        let (product, _) = Product::new(name, fields).to_foreign(forall!(a)).unwrap();
+        decls.declare_product(product);
+    }
+
+    let variances = decls.variances();
+    snap!(variances
+        .iter()
+        .map(|(constr, vs)| format!("{}: {}", constr, vs.iter().format(" ")))
+        .sorted()
+        .format("\n"));
+}
+
+#[test]
+fn match_exhaustiveness() {
+    let mut decls = type_decls();
+    let (opt, _) = Sum::new("option", vec![vec![], vec![a()]])
+        .to_foreign(forall!(a))
+        .unwrap();
+    decls.declare_sum(opt);
+
+    let none = || Pattern::Variant(0, vec![]);
+    let some = |p| Pattern::Variant(1, vec![p]);
+
+    let nested = decls
+        .check_match(
+            &option([option([int()])]),
+            &[some(some(Pattern::Wildcard)), none()],
+        )
+        .unwrap();
+    assert!(nested.unreachable.is_empty());
+
+    let redundant = decls
+        .check_match(
+            &result([int(), int()]),
+            &[
+                Pattern::Variant(0, vec![Pattern::Wildcard]),
+                Pattern::Variant(1, vec![Pattern::Wildcard]),
+                Pattern::Wildcard,
+            ],
+        )
+        .unwrap();
+    assert!(redundant.missing.is_empty());
+    assert_eq!(redundant.unreachable, vec![2]);
+
+    let fields = decls
+        .check_match(
+            &list([option([int()])]),
+            &[
+                Pattern::Product(vec![("head", some(Pattern::Wildcard))]),
+                Pattern::Product(vec![("tail", Pattern::Wildcard)]),
+                Pattern::Product(vec![("head", none())]),
+            ],
+        )
+        .unwrap();
+    assert_eq!(fields.unreachable, vec![2]);
+
+    let partial = decls
+        .check_match(
+            &list([option([int()])]),
+            &[Pattern::Product(vec![("head", some(Pattern::Wildcard))])],
+        )
+        .unwrap();
+
+    snap!([nested, fields, partial]
+        .iter()
+        .map(|report| report.missing.iter().format(", "))
+        .format("\n"));
+}
+
+#[test]
+fn match_ill_typed_patterns() {
+    let decls = type_decls();
+    let errors = decls
+        .check_match(
+            &list([int()]),
+            &[
+                Pattern::Variant(0, vec![]),
+                Pattern::Product(vec![("head", Pattern::Variant(0, vec![]))]),
+                Pattern::Product(vec![("size", Pattern::Wildcard)]),
+                Pattern::Wildcard,
+            ],
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        &errors[..],
+        [
+            (0, PatternError::Missmatch(_)),
+            (1, PatternError::Missmatch(_)),
+            (2, PatternError::UnknownField(_)),
+        ]
+    ));
+}
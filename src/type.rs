@@ -1,4 +1,4 @@
-use crate::{Generics, RefID, TypesBuf};
+use crate::{Generics, OpaqueID, RefID, SkolemID, TypesBuf};
 use itertools::Itertools;
 use std::fmt;
 use std::hash::Hash;
@@ -8,6 +8,12 @@ pub trait TypeData: Sized + fmt::Debug + Clone + PartialEq {
     type Generic: Key;
     type Trait: Key;
     type Association: Key;
+    /// Name of a trait method, used when resolving method calls by name
+    type Method: Key;
+    /// User-defined identifier of a function, used to refer to the bodies of trait methods
+    type Function: Key;
+    /// Name of a field of a product type
+    type Field: Key;
 
     type Meta: Clone + fmt::Debug + Default;
 
@@ -54,6 +60,15 @@ pub enum TypeKind<D: TypeData> {
     //
     // this would not be allowed because there's no V-table for `None`
     Object(D::Trait),
+    /// A type only known through the constraints it was declared with
+    Opaque(OpaqueID),
+    /// A polymorphic type quantifying over the generics of its single parameter, such as the
+    /// parameter of `(∀a. a -> a) -> int`
+    Forall(Vec<D::Generic>),
+    /// A generic made rigid within the scope declaring it, see [`crate::TEnv::enter_scope`]
+    Skolem(SkolemID),
+    /// An inference hole in a declared signature, see [`Type::hole`]
+    Hole,
 }
 
 impl<D: TypeData> Type<D> {
@@ -97,6 +112,14 @@ impl<D: TypeData> Type<D> {
         }
     }
 
+    pub fn forall(meta: D::Meta, bound: Vec<D::Generic>, body: Type<D>) -> Self {
+        Self {
+            meta,
+            constr: TypeKind::Forall(bound),
+            params: vec![body],
+        }
+    }
+
     pub fn reference(meta: D::Meta, rid: RefID, hkt: TypesBuf<D>) -> Self {
         Self {
             params: hkt,
@@ -105,6 +128,18 @@ impl<D: TypeData> Type<D> {
         }
     }
 
+    /// An inference hole to be filled in from an expected signature, such as `_` in an impl's
+    /// method signature
+    ///
+    /// Holes are replaced with fresh references when the signature is instantiated.
+    pub fn hole(meta: D::Meta, params: TypesBuf<D>) -> Self {
+        Self {
+            params,
+            meta,
+            constr: TypeKind::Hole,
+        }
+    }
+
     pub fn direct_eq(&self, other: &Self) -> bool {
         self.constr == other.constr
             && self
@@ -123,6 +158,10 @@ impl<D: TypeData> TypeKind<D> {
             TypeKind::Object(_) => "trait object",
             TypeKind::Generic(_) => "generic",
             TypeKind::Self_ => "self",
+            TypeKind::Hole => "hole",
+            TypeKind::Forall(_) => "quantified",
+            TypeKind::Opaque(_) => "opaque",
+            TypeKind::Skolem(_) => "rigid",
         }
     }
 }
@@ -133,11 +172,20 @@ impl<D: TypeData> fmt::Display for Type<D> {
             TypeKind::Concrete(c) => D::fmt_specific(c, self, f),
             TypeKind::Generic(g) => self.fmt_with_params(g, f),
             TypeKind::Object(trid) => self.fmt_with_params(format!("dyn {}", trid), f),
+            TypeKind::Hole => self.fmt_with_params("_", f),
             TypeKind::Ref(rid) if *rid > 25 => self.fmt_with_params(format!("'{}", rid), f),
             TypeKind::Ref(rid) => {
                 self.fmt_with_params(format!("'{}", (*rid as u8 + b'a') as char), f)
             }
             TypeKind::Self_ => self.fmt_with_params("self", f),
+            TypeKind::Opaque(id) => self.fmt_with_params(id, f),
+            TypeKind::Skolem(id) => self.fmt_with_params(id, f),
+            TypeKind::Forall(bound) => write!(
+                f,
+                "(∀{}. {})",
+                bound.iter().format(", "),
+                self.params.iter().format(" ")
+            ),
         }
     }
 }
@@ -148,11 +196,15 @@ impl<D: TypeData> fmt::Display for TypeKind<D> {
             TypeKind::Concrete(c) => c.fmt(f),
             TypeKind::Generic(g) => g.fmt(f),
             TypeKind::Object(trid) => trid.fmt(f),
+            TypeKind::Hole => "_".fmt(f),
             TypeKind::Ref(rid) if *rid > 25 => write!(f, "'{}", rid),
             TypeKind::Ref(rid) => {
                 write!(f, "'{}", (*rid as u8 + b'a') as char)
             }
             TypeKind::Self_ => "self".fmt(f),
+            TypeKind::Opaque(id) => id.fmt(f),
+            TypeKind::Skolem(id) => id.fmt(f),
+            TypeKind::Forall(bound) => write!(f, "∀{}", bound.iter().format(", ")),
         }
     }
 }
@@ -0,0 +1,204 @@
+use crate::{Generics, Type, TypeData, TypeDecl, TypeDecls, TypeKind};
+use std::collections::HashMap;
+use std::fmt;
+
+/// How the subtyping of a type parameter relates to the subtyping of the type it's given to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variance {
+    /// `a ⊑ b` implies `(t a) ⊑ (t b)`
+    Covariant,
+    /// `a ⊑ b` implies `(t b) ⊑ (t a)`
+    Contravariant,
+    /// `(t a) ⊑ (t b)` only if `a` and `b` are equal
+    Invariant,
+    /// The parameter is never used, so any two instantiations are related
+    Bivariant,
+}
+
+impl Variance {
+    /// The least variance accepting both usages
+    pub fn join(self, other: Variance) -> Variance {
+        match (self, other) {
+            (Variance::Bivariant, v) | (v, Variance::Bivariant) => v,
+            (l, r) if l == r => l,
+            _ => Variance::Invariant,
+        }
+    }
+
+    /// The variance of a usage with variance `inner`, which itself is in a position of this
+    /// variance
+    pub fn transform(self, inner: Variance) -> Variance {
+        match (self, inner) {
+            (Variance::Bivariant, _) | (_, Variance::Bivariant) => Variance::Bivariant,
+            (Variance::Covariant, v) => v,
+            (Variance::Contravariant, v) => v.flip(),
+            (Variance::Invariant, _) => Variance::Invariant,
+        }
+    }
+
+    pub fn flip(self) -> Variance {
+        match self {
+            Variance::Covariant => Variance::Contravariant,
+            Variance::Contravariant => Variance::Covariant,
+            v => v,
+        }
+    }
+}
+
+/// The infered variances of the generics of all declared types
+#[derive(Debug, Clone)]
+pub struct Variances<D: TypeData> {
+    decls: HashMap<D::Concrete, Vec<Variance>>,
+}
+
+impl<D: TypeData> Variances<D> {
+    /// The variances of the type's parameters, in the order its generics were declared
+    pub fn of(&self, constr: &D::Concrete) -> Option<&[Variance]> {
+        self.decls.get(constr).map(Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&D::Concrete, &[Variance])> {
+        self.decls
+            .iter()
+            .map(|(constr, variances)| (constr, variances.as_slice()))
+    }
+}
+
+impl<D: TypeData> TypeDecls<D> {
+    /// Infer the variance of every declared type's generics.
+    ///
+    /// Recursive declarations are resolved by starting every generic as bivariant and widening
+    /// them until none change. Types without a declaration or declared variance are assumed to
+    /// be invariant in all their parameters.
+    pub fn variances(&self) -> Variances<D> {
+        let mut variances = Variances {
+            decls: self
+                .iter()
+                .map(|(constr, decl)| (constr.clone(), vec![Variance::Bivariant; decl.arity()]))
+                .collect(),
+        };
+
+        loop {
+            let mut changed = false;
+
+            for (constr, decl) in self.iter() {
+                let mut infered = vec![Variance::Bivariant; decl.arity()];
+                let generics = decl.generics();
+
+                match decl {
+                    TypeDecl::Product(product) => {
+                        for t in product.product.field_types() {
+                            let mut walker = Walker::new(self, &variances, generics, &mut infered);
+                            walker.type_(t, Variance::Covariant);
+                        }
+                    }
+                    TypeDecl::Sum(sum) => {
+                        let variants = sum.sum.variants().iter().zip(sum.sum.existentials());
+                        for (params, existentials) in variants {
+                            let mut walker = Walker::new(self, &variances, generics, &mut infered)
+                                .shadowed(existentials);
+                            for t in params {
+                                walker.type_(t, Variance::Covariant);
+                            }
+                        }
+                    }
+                }
+
+                let previous = variances.decls.get_mut(constr).unwrap();
+                if *previous != infered {
+                    *previous = infered;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break variances;
+            }
+        }
+    }
+}
+
+struct Walker<'a, D: TypeData> {
+    decls: &'a TypeDecls<D>,
+    variances: &'a Variances<D>,
+    generics: &'a Generics<D>,
+    shadowed: Option<&'a Generics<D>>,
+    infered: &'a mut [Variance],
+}
+
+impl<'a, D: TypeData> Walker<'a, D> {
+    fn new(
+        decls: &'a TypeDecls<D>,
+        variances: &'a Variances<D>,
+        generics: &'a Generics<D>,
+        infered: &'a mut [Variance],
+    ) -> Self {
+        Self {
+            decls,
+            variances,
+            generics,
+            shadowed: None,
+            infered,
+        }
+    }
+
+    fn shadowed(mut self, local: &'a Generics<D>) -> Self {
+        self.shadowed = Some(local);
+        self
+    }
+
+    fn type_(&mut self, t: &Type<D>, position: Variance) {
+        match &t.constr {
+            TypeKind::Generic(gid) => {
+                let shadowed = self
+                    .shadowed
+                    .is_some_and(|local| local.contains(gid.clone()));
+                if let Some(i) = self.generics.position(gid.clone()).filter(|_| !shadowed) {
+                    self.infered[i] = self.infered[i].join(position);
+                }
+                self.params(t, |_| Variance::Invariant, position);
+            }
+            TypeKind::Concrete(constr) => {
+                let declared = self
+                    .variances
+                    .of(constr)
+                    .or_else(|| self.decls.builtin_variance(constr));
+                self.params(
+                    t,
+                    |i| {
+                        declared
+                            .and_then(|vs| vs.get(i).copied())
+                            .unwrap_or(Variance::Invariant)
+                    },
+                    position,
+                );
+            }
+            // The body of a quantified type is used just like the quantified type itself
+            TypeKind::Forall(_) => self.type_(&t.params[0], position),
+            TypeKind::Ref(_)
+            | TypeKind::Hole
+            | TypeKind::Self_
+            | TypeKind::Object(_)
+            | TypeKind::Opaque(_)
+            | TypeKind::Skolem(_) => self.params(t, |_| Variance::Invariant, position),
+        }
+    }
+
+    fn params(&mut self, t: &Type<D>, variance: impl Fn(usize) -> Variance, position: Variance) {
+        for (i, param) in t.params.iter().enumerate() {
+            self.type_(param, position.transform(variance(i)));
+        }
+    }
+}
+
+impl fmt::Display for Variance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Variance::Covariant => "+",
+            Variance::Contravariant => "-",
+            Variance::Invariant => "=",
+            Variance::Bivariant => "*",
+        }
+        .fmt(f)
+    }
+}
@@ -17,6 +17,12 @@ pub trait TypeVisitor<D: TypeData> {
     }
 }
 
+impl<D: TypeData> TypeVisitor<D> for Type<D> {
+    fn map_types<F: FnMut(D::Meta, &TypeKind<D>, TypesBuf<D>) -> Type<D>>(&self, mut f: F) -> Self {
+        self.map_type(&mut f)
+    }
+}
+
 impl<D: TypeData> Type<D> {
     pub fn map_constr<
         O: TypeData<Meta = D::Meta>,
@@ -66,6 +72,39 @@ impl<D: TypeData> Type<D> {
         f(self.meta, self.constr, params)
     }
 
+    /// Like `map_constr`, but skips over the generics bound by quantifiers within the type
+    pub fn map_free_constr<F: FnMut(&TypeKind<D>) -> TypeKind<D>>(&self, f: &mut F) -> Type<D> {
+        self.map_free_constr_bound(&mut vec![], f)
+    }
+
+    fn map_free_constr_bound<F: FnMut(&TypeKind<D>) -> TypeKind<D>>(
+        &self,
+        bound: &mut Vec<D::Generic>,
+        f: &mut F,
+    ) -> Type<D> {
+        let constr = match &self.constr {
+            TypeKind::Generic(gid) if bound.contains(gid) => self.constr.clone(),
+            constr => f(constr),
+        };
+
+        let previous = bound.len();
+        if let TypeKind::Forall(gids) = &self.constr {
+            bound.extend(gids.iter().cloned());
+        }
+        let params = self
+            .params
+            .iter()
+            .map(|t| t.map_free_constr_bound(bound, f))
+            .collect();
+        bound.truncate(previous);
+
+        Type {
+            constr,
+            meta: self.meta.clone(),
+            params,
+        }
+    }
+
     pub fn substitute_self_and_meta(&self, impltor: &Type<D>, or_meta: D::Meta) -> Type<D> {
         self.map_type(&mut |_, constr, params| match constr {
             TypeKind::Self_ => {
@@ -0,0 +1,120 @@
+use crate::decls::substitute;
+use crate::frontend::ForeignFunction;
+use crate::{
+    check, ErrorHandler, Generics, TEnv, TraitIndex, Type, TypeContext, TypeData, TypeDecls,
+    TypeKind,
+};
+
+/// A type which doesn't satisfy the declaration of one of its type constructors
+#[derive(Debug, Clone)]
+pub enum WfError<D: TypeData> {
+    /// The type parameter at index `param` of `type_` does not meet the constraints of the
+    /// declared generic it's given for
+    Unsatisfied {
+        type_: Type<D>,
+        param: usize,
+        error: check::Error<D>,
+    },
+    /// The type was given more parameters than it has generics, see
+    /// [`check::Error::ArityMissmatch`]
+    Arity(check::Error<D>),
+}
+
+impl<D: TypeData> TypeDecls<D> {
+    /// Check that every declared type constructor in the type is given parameters satisfying the
+    /// constraints of its declaration.
+    ///
+    /// Generics are only assumed to satisfy constraints if they're rigid in the current scope, and
+    /// unresolved references will have the constraints added to them.
+    pub fn well_formed(
+        &self,
+        tenv: &mut TEnv<D>,
+        traits: &TraitIndex<D>,
+        t: &Type<D>,
+    ) -> Result<(), Vec<WfError<D>>> {
+        let mut errors = vec![];
+        self.wf_type(tenv, traits, t, &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check the well-formedness of a signature with its own generics assumed to satisfy their
+    /// declared constraints
+    pub fn well_formed_function(
+        &self,
+        tenv: &mut TEnv<D>,
+        traits: &TraitIndex<D>,
+        f: &ForeignFunction<D>,
+    ) -> Result<(), Vec<WfError<D>>> {
+        let scope = tenv.enter_scope(&f.generics);
+        let mut errors = vec![];
+
+        let sig = &f.function;
+        let constraint_params = f
+            .generics
+            .iter()
+            .flat_map(|(_, constrs)| constrs.iter().flat_map(|con| con.params.iter()));
+        for t in constraint_params
+            .chain(sig.ptypes.iter())
+            .chain(std::iter::once(&sig.returns))
+        {
+            self.wf_type(tenv, traits, &scope.apply_type(t), &mut errors);
+        }
+
+        tenv.leave_scope();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn wf_type(
+        &self,
+        tenv: &mut TEnv<D>,
+        traits: &TraitIndex<D>,
+        t: &Type<D>,
+        errors: &mut Vec<WfError<D>>,
+    ) {
+        for param in &t.params {
+            self.wf_type(tenv, traits, param, errors);
+        }
+
+        let decl = match &t.constr {
+            TypeKind::Concrete(constr) => match self.get(constr) {
+                Some(decl) => decl,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let mut tctx = TypeContext::new(tenv, traits, ErrorHandler::Expensive).with_decls(self);
+        if let Err(error) = tctx.arity(t) {
+            errors.push(WfError::Arity(error));
+            return;
+        }
+
+        let generics = decl.generics();
+        for (param, ((_, constrs), given)) in generics.iter().zip(&t.params).enumerate() {
+            // Constraints referring to generics which weren't given can't be checked yet
+            let constrs = constrs
+                .iter()
+                .filter_map(|con| substitute(generics, &Generics::new(), &t.params, con));
+
+            for con in constrs {
+                if let Err(error) = tctx.constrain(given, &con) {
+                    errors.push(WfError::Unsatisfied {
+                        type_: t.clone(),
+                        param,
+                        error,
+                    });
+                    break;
+                }
+            }
+        }
+    }
+}